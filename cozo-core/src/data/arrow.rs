@@ -0,0 +1,77 @@
+/*
+ * Copyright 2023, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, BooleanArray, Float32Array, Float64Array, Int64Array, StringArray};
+use miette::{bail, Diagnostic, Result};
+use thiserror::Error;
+
+use crate::data::relation::{ColType, NullableColType};
+use crate::data::value::DataValue;
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("cannot put value {1:?} into an Arrow array of type {0}")]
+#[diagnostic(code(eval::arrow_bad_value))]
+struct BadArrowValue(NullableColType, DataValue);
+
+fn collect_column<'a, T>(
+    values: &'a [DataValue],
+    ty: &NullableColType,
+    get: impl Fn(&'a DataValue) -> Option<T>,
+) -> Result<Vec<Option<T>>> {
+    values
+        .iter()
+        .map(|v| match v {
+            DataValue::Null if ty.nullable => Ok(None),
+            v => match get(v) {
+                Some(el) => Ok(Some(el)),
+                None => bail!(BadArrowValue(ty.clone(), v.clone())),
+            },
+        })
+        .collect()
+}
+
+impl DataValue {
+    /// Builds an Arrow array out of a column of values, all of which must be of type `ty`.
+    /// Only `Bool`, `Int`, `Float`, `Float32` and string columns are supported.
+    /// Nulls become null slots if `ty` is nullable.
+    pub fn to_arrow_array(values: &[DataValue], ty: &NullableColType) -> Result<ArrayRef> {
+        Ok(match ty.coltype {
+            ColType::Bool => Arc::new(BooleanArray::from(collect_column(
+                values,
+                ty,
+                DataValue::get_bool,
+            )?)),
+            ColType::Int => Arc::new(Int64Array::from(collect_column(
+                values,
+                ty,
+                DataValue::get_int,
+            )?)),
+            ColType::Float => Arc::new(Float64Array::from(collect_column(
+                values,
+                ty,
+                DataValue::get_float,
+            )?)),
+            ColType::Float32 => Arc::new(Float32Array::from(collect_column(values, ty, |v| {
+                v.get_float().map(|f| f as f32)
+            })?)),
+            ColType::String | ColType::BoundedString { .. } => Arc::new(StringArray::from(
+                collect_column(values, ty, DataValue::get_str)?,
+            )),
+            _ => {
+                #[derive(Debug, Error, Diagnostic)]
+                #[error("type {0} has no corresponding Arrow array")]
+                #[diagnostic(code(eval::arrow_unsupported_type))]
+                struct UnsupportedArrowType(NullableColType);
+
+                bail!(UnsupportedArrowType(ty.clone()))
+            }
+        })
+    }
+}
@@ -6,14 +6,309 @@
  * You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
+use std::io::Write;
+
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use itertools::Itertools;
+use miette::{bail, ensure, Diagnostic, IntoDiagnostic, Result};
 use serde_json::json;
 pub(crate) use serde_json::Value as JsonValue;
+use thiserror::Error;
 
+use crate::data::relation::NullableColType;
 use crate::data::value::{DataValue, Num, Vector};
 use crate::JsonData;
 
+/// Largest integer that JavaScript can represent exactly as a number
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Options for converting between [DataValue] and JSON,
+/// used by [datavalue_to_json_with_opts] and [datavalue_from_json_with_opts].
+/// The defaults give the same results as the plain `From` conversions.
+#[derive(Debug, Clone, Default)]
+pub struct JsonOptions {
+    /// Emit ints outside JavaScript's safe integer range as `{"$int": "<digits>"}`,
+    /// and read such tags back as ints
+    pub tag_large_ints: bool,
+    /// Emit bytes as `{"$bytes": "<base64>"}` instead of a bare base64 string,
+    /// and read such tags back as bytes
+    pub tag_bytes: bool,
+    /// Round finite floats to this many fractional digits
+    pub float_digits: Option<usize>,
+    /// How to write floats that are NaN or infinite
+    pub non_finite_floats: NonFiniteFloatPolicy,
+    /// How to write the timestamps of validities
+    pub timestamp_format: TimestampFormat,
+    /// Read integers beyond the range of `i64`, such as large `u64` IDs, as Json numbers
+    /// that keep every bit, instead of as Floats that still compute as numbers
+    pub exact_large_ints: bool,
+}
+
+/// How [datavalue_to_json_with_opts] writes the timestamps of validities.
+/// Validity columns accept both forms back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// Microseconds since the UNIX epoch, as in the plain `From` conversion
+    #[default]
+    Epoch,
+    /// RFC 3339 string in UTC such as `2023-01-01T00:00:00Z`,
+    /// with as many fractional digits as needed for microseconds
+    Iso8601,
+}
+
+fn format_micros_as_iso8601(micros: i64) -> String {
+    let secs = micros.div_euclid(1_000_000);
+    let nanos = micros.rem_euclid(1_000_000) as u32 * 1000;
+    match NaiveDateTime::from_timestamp_opt(secs, nanos) {
+        Some(dt) => DateTime::<Utc>::from_utc(dt, Utc).to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        // out of the range of dates, which can only be a sentinel
+        None => micros.to_string(),
+    }
+}
+
+/// How [datavalue_to_json_with_opts] writes floats that are NaN or infinite,
+/// none of which JSON numbers can represent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonFiniteFloatPolicy {
+    /// NaN becomes `null` and infinities become the strings `"INFINITY"` and
+    /// `"NEGATIVE_INFINITY"`, as in the plain `From` conversion
+    #[default]
+    Plain,
+    /// Write `null`
+    Null,
+    /// Fail the conversion
+    Error,
+    /// Write `{"$float": "NaN"}`, `{"$float": "Infinity"}` or `{"$float": "-Infinity"}`,
+    /// and read such tags back as floats
+    Tagged,
+}
+
+fn float_to_json(f: f64, opts: &JsonOptions) -> Result<JsonValue> {
+    #[derive(Debug, Error, Diagnostic)]
+    #[error("cannot convert float {0} to JSON")]
+    #[diagnostic(code(eval::non_finite_float_to_json))]
+    struct NonFiniteFloat(f64);
+
+    if f.is_finite() {
+        return Ok(match opts.float_digits {
+            Some(digits) => json!(format!("{f:.digits$}").parse::<f64>().unwrap()),
+            None => json!(f),
+        });
+    }
+    Ok(match opts.non_finite_floats {
+        NonFiniteFloatPolicy::Plain => JsonValue::from(DataValue::from(f)),
+        NonFiniteFloatPolicy::Null => JsonValue::Null,
+        NonFiniteFloatPolicy::Error => bail!(NonFiniteFloat(f)),
+        NonFiniteFloatPolicy::Tagged => {
+            let tag = if f.is_nan() {
+                "NaN"
+            } else if f.is_sign_positive() {
+                "Infinity"
+            } else {
+                "-Infinity"
+            };
+            json!({ "$float": tag })
+        }
+    })
+}
+
+/// Converts a [DataValue] into JSON according to `opts`.
+/// Fails only for non-finite floats under [NonFiniteFloatPolicy::Error].
+pub fn datavalue_to_json_with_opts(v: DataValue, opts: &JsonOptions) -> Result<JsonValue> {
+    Ok(match v {
+        DataValue::Num(Num::Int(i))
+            if opts.tag_large_ints && i.unsigned_abs() > MAX_SAFE_INTEGER =>
+        {
+            json!({"$int": i.to_string()})
+        }
+        DataValue::Bytes(b) if opts.tag_bytes => json!({"$bytes": STANDARD.encode(b)}),
+        DataValue::Num(Num::Float(f)) => float_to_json(f, opts)?,
+        DataValue::Validity(vld) if opts.timestamp_format == TimestampFormat::Iso8601 => {
+            json!([
+                format_micros_as_iso8601(vld.timestamp.0 .0),
+                vld.is_assert.0
+            ])
+        }
+        DataValue::Vec(v)
+            if opts.float_digits.is_some()
+                || opts.non_finite_floats != NonFiniteFloatPolicy::Plain =>
+        {
+            let floats = match v {
+                Vector::F32(a) => a.iter().map(|f| *f as f64).collect_vec(),
+                Vector::F64(a) => a.to_vec(),
+            };
+            JsonValue::Array(
+                floats
+                    .into_iter()
+                    .map(|f| float_to_json(f, opts))
+                    .collect::<Result<_>>()?,
+            )
+        }
+        DataValue::List(l) => JsonValue::Array(
+            l.into_iter()
+                .map(|v| datavalue_to_json_with_opts(v, opts))
+                .collect::<Result<_>>()?,
+        ),
+        DataValue::Set(l) => JsonValue::Array(
+            l.into_iter()
+                .map(|v| datavalue_to_json_with_opts(v, opts))
+                .collect::<Result<_>>()?,
+        ),
+        v => JsonValue::from(v),
+    })
+}
+
+impl DataValue {
+    /// Converts JSON into a value of type `typ`, e.g. parsing strings into UUIDs
+    /// for `Uuid`. Errors name the JSON path of any element that does not fit.
+    pub fn from_json_with_schema(input: &JsonValue, typ: &NullableColType) -> Result<DataValue> {
+        typ.coerce_json(input)
+    }
+    /// Converts into JSON with finite floats rounded to `digits` fractional digits,
+    /// anywhere inside Lists and Sets. The floats are still JSON numbers, so trailing
+    /// zeros are not written.
+    pub fn to_json_with_float_format(&self, digits: usize) -> JsonValue {
+        let opts = JsonOptions {
+            float_digits: Some(digits),
+            ..Default::default()
+        };
+        // only NonFiniteFloatPolicy::Error can fail
+        datavalue_to_json_with_opts(self.clone(), &opts).unwrap()
+    }
+    /// Writes each element of a List as one line of JSON, for newline-delimited JSON
+    /// export. Fails for anything other than a List.
+    pub fn write_ndjson(&self, w: &mut impl Write) -> Result<()> {
+        #[derive(Debug, Error, Diagnostic)]
+        #[error("only a list can be written as NDJSON, got {0:?}")]
+        #[diagnostic(code(eval::ndjson_not_a_list))]
+        struct NdjsonNotAList(DataValue);
+
+        let l = match self {
+            DataValue::List(l) => l,
+            v => bail!(NdjsonNotAList(v.clone())),
+        };
+        for el in l {
+            serde_json::to_writer(&mut *w, &JsonValue::from(el.clone())).into_diagnostic()?;
+            w.write_all(b"\n").into_diagnostic()?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts JSON into a [DataValue] according to `opts`.
+/// Fails if a tag recognized under `opts` has a malformed payload.
+/// Like the `From` conversions, keeps arrays nested more than 128 levels deep as Json.
+pub fn datavalue_from_json_with_opts(v: &JsonValue, opts: &JsonOptions) -> Result<DataValue> {
+    from_json_with_opts_at(v, opts, MAX_JSON_LIST_DEPTH)
+}
+
+fn from_json_with_opts_at(v: &JsonValue, opts: &JsonOptions, depth: usize) -> Result<DataValue> {
+    #[derive(Debug, Error, Diagnostic)]
+    #[error("bad payload for JSON tag {0}: {1}")]
+    #[diagnostic(code(eval::bad_json_tag))]
+    struct BadJsonTag(&'static str, JsonValue);
+
+    Ok(match v {
+        JsonValue::Object(o) if opts.tag_large_ints && o.len() == 1 && o.contains_key("$int") => {
+            match o["$int"].as_str().and_then(|s| s.parse::<i64>().ok()) {
+                Some(i) => DataValue::from(i),
+                None => bail!(BadJsonTag("$int", o["$int"].clone())),
+            }
+        }
+        JsonValue::Object(o) if opts.tag_bytes && o.len() == 1 && o.contains_key("$bytes") => {
+            match o["$bytes"].as_str().and_then(|s| STANDARD.decode(s).ok()) {
+                Some(b) => DataValue::Bytes(b),
+                None => bail!(BadJsonTag("$bytes", o["$bytes"].clone())),
+            }
+        }
+        JsonValue::Object(o)
+            if opts.non_finite_floats == NonFiniteFloatPolicy::Tagged
+                && o.len() == 1
+                && o.contains_key("$float") =>
+        {
+            match o["$float"].as_str() {
+                Some("NaN") => DataValue::from(f64::NAN),
+                Some("Infinity") => DataValue::from(f64::INFINITY),
+                Some("-Infinity") => DataValue::from(f64::NEG_INFINITY),
+                _ => bail!(BadJsonTag("$float", o["$float"].clone())),
+            }
+        }
+        JsonValue::Number(n) if opts.exact_large_ints && n.as_i64().is_none() && n.is_u64() => {
+            DataValue::Json(JsonData(v.clone()))
+        }
+        JsonValue::Array(arr) if depth > 0 => DataValue::List(
+            arr.iter()
+                .map(|v| from_json_with_opts_at(v, opts, depth - 1))
+                .collect::<Result<_>>()?,
+        ),
+        v @ JsonValue::Array(_) => DataValue::Json(JsonData(v.clone())),
+        v => DataValue::from(v),
+    })
+}
+
+/// Arrays nested deeper than this are kept as JSON by the `From` conversions,
+/// so that hostile input cannot overflow the stack
+const MAX_JSON_LIST_DEPTH: usize = 128;
+
+fn json_array_to_list(v: JsonValue, depth: usize) -> DataValue {
+    match v {
+        JsonValue::Array(arr) if depth > 0 => DataValue::List(
+            arr.into_iter()
+                .map(|el| json_array_to_list(el, depth - 1))
+                .collect(),
+        ),
+        v @ JsonValue::Array(_) => DataValue::Json(JsonData(v)),
+        v => DataValue::from(v),
+    }
+}
+
+fn json_array_ref_to_list(v: &JsonValue, depth: usize) -> DataValue {
+    match v {
+        JsonValue::Array(arr) if depth > 0 => DataValue::List(
+            arr.iter()
+                .map(|el| json_array_ref_to_list(el, depth - 1))
+                .collect(),
+        ),
+        v @ JsonValue::Array(_) => DataValue::Json(JsonData(v.clone())),
+        v => DataValue::from(v),
+    }
+}
+
+impl DataValue {
+    /// Converts JSON into a value like the `From` conversion, but fails if arrays are
+    /// nested more than `max_depth` deep. The `From` conversion instead keeps arrays
+    /// nested beyond 128 levels as Json.
+    pub fn try_from_json_bounded(v: &JsonValue, max_depth: usize) -> Result<DataValue> {
+        #[derive(Debug, Error, Diagnostic)]
+        #[error("JSON arrays are nested more than {0} deep")]
+        #[diagnostic(code(eval::json_too_deep))]
+        struct JsonTooDeep(usize);
+
+        fn convert(v: &JsonValue, depth: usize, max_depth: usize) -> Result<DataValue> {
+            match v {
+                JsonValue::Array(arr) => {
+                    ensure!(depth < max_depth, JsonTooDeep(max_depth));
+                    Ok(DataValue::List(
+                        arr.iter()
+                            .map(|el| convert(el, depth + 1, max_depth))
+                            .collect::<Result<_>>()?,
+                    ))
+                }
+                v => Ok(DataValue::from(v)),
+            }
+        }
+
+        convert(v, 0, max_depth)
+    }
+}
+
+/// Arrays become Lists and objects become Json. Integers beyond the range of `i64`
+/// become Floats, losing precision; [datavalue_from_json_with_opts] with
+/// [JsonOptions::exact_large_ints] keeps them exact instead. Arrays nested more than
+/// 128 levels deep are not rejected but kept as Json from that depth on; use
+/// [DataValue::try_from_json_bounded] to fail on them instead.
 impl From<JsonValue> for DataValue {
     fn from(v: JsonValue) -> Self {
         match v {
@@ -21,18 +316,22 @@ impl From<JsonValue> for DataValue {
             JsonValue::Bool(b) => DataValue::Bool(b),
             JsonValue::Number(n) => match n.as_i64() {
                 Some(i) => DataValue::from(i),
+                // ints beyond i64 become floats, losing precision but still numbers
                 None => match n.as_f64() {
                     Some(f) => DataValue::from(f),
                     None => DataValue::from(n.to_string()),
                 },
             },
             JsonValue::String(s) => DataValue::from(s),
-            JsonValue::Array(arr) => DataValue::List(arr.iter().map(DataValue::from).collect()),
+            v @ JsonValue::Array(_) => json_array_to_list(v, MAX_JSON_LIST_DEPTH),
             JsonValue::Object(d) => DataValue::Json(JsonData(JsonValue::Object(d))),
         }
     }
 }
 
+/// Converts like `From<JsonValue>`, including turning integers beyond `i64` into Floats and
+/// keeping arrays nested more than 128 levels deep as Json, but only clones what ends up
+/// in the value.
 impl<'a> From<&'a JsonValue> for DataValue {
     fn from(v: &'a JsonValue) -> Self {
         match v {
@@ -40,13 +339,14 @@ impl<'a> From<&'a JsonValue> for DataValue {
             JsonValue::Bool(b) => DataValue::Bool(*b),
             JsonValue::Number(n) => match n.as_i64() {
                 Some(i) => DataValue::from(i),
+                // ints beyond i64 become floats, losing precision but still numbers
                 None => match n.as_f64() {
                     Some(f) => DataValue::from(f),
                     None => DataValue::from(n.to_string()),
                 },
             },
             JsonValue::String(s) => DataValue::Str(s.into()),
-            JsonValue::Array(arr) => DataValue::List(arr.iter().map(DataValue::from).collect()),
+            v @ JsonValue::Array(_) => json_array_ref_to_list(v, MAX_JSON_LIST_DEPTH),
             JsonValue::Object(d) => DataValue::Json(JsonData(JsonValue::Object(d.clone()))),
         }
     }
@@ -75,13 +375,9 @@ impl From<DataValue> for JsonValue {
             }
             DataValue::Str(t) => JsonValue::String(t.into()),
             DataValue::Bytes(bytes) => JsonValue::String(STANDARD.encode(bytes)),
-            DataValue::List(l) => {
-                JsonValue::Array(l.iter().map(|v| JsonValue::from(v.clone())).collect())
-            }
+            DataValue::List(l) => JsonValue::Array(l.into_iter().map(JsonValue::from).collect()),
             DataValue::Bot => panic!("found bottom"),
-            DataValue::Set(l) => {
-                JsonValue::Array(l.iter().map(|v| JsonValue::from(v.clone())).collect())
-            }
+            DataValue::Set(l) => JsonValue::Array(l.into_iter().map(JsonValue::from).collect()),
             DataValue::Regex(r) => {
                 json!(r.0.as_str())
             }
@@ -12,7 +12,9 @@ use std::io::Write;
 use std::str::FromStr;
 
 use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use miette::{ensure, Diagnostic, Result};
 use regex::Regex;
+use thiserror::Error;
 
 use crate::data::value::{
     DataValue, JsonData, Num, RegexWrapper, UuidWrapper, Validity, ValidityTs, Vector,
@@ -368,4 +370,115 @@ impl DataValue {
     }
 }
 
+impl DataValue {
+    /// Encodes this value as a key whose bytewise order agrees with the order of the values,
+    /// as used for the keys of stored relations. The exceptions are `Vec`, which sorts before
+    /// the numbers and whose elements are not encoded order-preservingly, and `Json`, which sorts
+    /// after `Validity`: their tags are part of the storage format. For descending order,
+    /// flip every byte of the encoding.
+    pub fn encode_as_key(&self) -> Vec<u8> {
+        let mut ret = vec![];
+        ret.encode_datavalue(self);
+        ret
+    }
+    /// Decodes a value from the output of [DataValue::encode_as_key]. Unlike the decoding of
+    /// stored tuples, this does not trust its input: truncated, trailing or otherwise malformed
+    /// bytes are an error.
+    pub fn decode_key(bs: &[u8]) -> Result<DataValue> {
+        #[derive(Debug, Error, Diagnostic)]
+        #[error("bytes are not a valid key encoding of a value")]
+        #[diagnostic(code(eval::bad_key_encoding))]
+        struct BadKeyEncoding;
+
+        ensure!(
+            matches!(check_key(bs), Some(rest) if rest.is_empty()),
+            BadKeyEncoding
+        );
+        let (val, _) = DataValue::decode_from_key(bs);
+        // non-canonical encodings, e.g. a fractional float tagged as an exact int, decode
+        // without complaint but to a different value
+        ensure!(val.encode_as_key() == bs, BadKeyEncoding);
+        Ok(val)
+    }
+}
+
+/// Walks over one encoded value without decoding it, checking that [DataValue::decode_from_key]
+/// will not panic on it. Returns the bytes after the value.
+fn check_key(bs: &[u8]) -> Option<&[u8]> {
+    let (tag, rest) = bs.split_first()?;
+    Some(match *tag {
+        NULL_TAG | FALSE_TAG | TRUE_TAG | BOT_TAG => rest,
+        NUM_TAG => {
+            let (num_tag, rest) = rest.get(8..)?.split_first()?;
+            match *num_tag {
+                IS_FLOAT | IS_EXACT_INT => rest,
+                IS_APPROX_INT => rest.get(8..)?,
+                _ => return None,
+            }
+        }
+        STR_TAG | BYTES_TAG | REGEX_TAG | JSON_TAG => {
+            let (bytes, rest) = check_bytes(rest)?;
+            match *tag {
+                STR_TAG => {
+                    std::str::from_utf8(&bytes).ok()?;
+                }
+                REGEX_TAG => {
+                    Regex::new(std::str::from_utf8(&bytes).ok()?).ok()?;
+                }
+                JSON_TAG => {
+                    serde_json::from_slice::<serde_json::Value>(&bytes).ok()?;
+                }
+                _ => {}
+            }
+            rest
+        }
+        UUID_TAG => rest.get(16..)?,
+        LIST_TAG | SET_TAG => {
+            let mut rest = rest;
+            loop {
+                if *rest.first()? == INIT_TAG {
+                    break &rest[1..];
+                }
+                rest = check_key(rest)?;
+            }
+        }
+        VLD_TAG => rest.get(9..)?,
+        VEC_TAG => {
+            let (t_tag, rest) = rest.split_first()?;
+            let width = match *t_tag {
+                VEC_F32 => 4,
+                VEC_F64 => 8,
+                _ => return None,
+            };
+            let len = BigEndian::read_u64(rest.get(..8)?) as usize;
+            rest[8..].get(len.checked_mul(width)?..)?
+        }
+        _ => return None,
+    })
+}
+
+fn check_bytes(data: &[u8]) -> Option<(Vec<u8>, &[u8])> {
+    let mut key = vec![];
+    let mut data = data;
+    loop {
+        let chunk = data.get(..ENC_GROUP_SIZE + 1)?;
+        data = &data[ENC_GROUP_SIZE + 1..];
+        let (&marker, bytes) = chunk.split_last()?;
+        let pad_size = (ENC_MARKER - marker) as usize;
+        if pad_size == 0 {
+            key.extend_from_slice(bytes);
+            continue;
+        }
+        if pad_size > ENC_GROUP_SIZE {
+            return None;
+        }
+        let (bytes, padding) = bytes.split_at(ENC_GROUP_SIZE - pad_size);
+        if padding.iter().any(|x| *x != 0) {
+            return None;
+        }
+        key.extend_from_slice(bytes);
+        return Some((key, data));
+    }
+}
+
 impl<T: Write> MemCmpEncoder for T {}
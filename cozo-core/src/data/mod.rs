@@ -7,6 +7,8 @@
  */
 
 pub(crate) mod aggr;
+#[cfg(feature = "arrow")]
+pub(crate) mod arrow;
 pub(crate) mod expr;
 pub(crate) mod functions;
 pub(crate) mod json;
@@ -16,6 +18,7 @@ pub(crate) mod relation;
 pub(crate) mod symb;
 pub(crate) mod tuple;
 pub(crate) mod value;
+pub(crate) mod wire;
 
 #[cfg(test)]
 mod tests;
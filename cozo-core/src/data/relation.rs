@@ -7,26 +7,32 @@
  */
 
 use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Display, Formatter};
 use std::mem;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
 use chrono::DateTime;
 use itertools::Itertools;
-use miette::{bail, ensure, Diagnostic, Result};
+use miette::{bail, ensure, Diagnostic, Report, Result, WrapErr};
 use serde_json::json;
 use smartstring::{LazyCompact, SmartString};
 use thiserror::Error;
 
 use crate::data::expr::Expr;
+use crate::data::functions::current_validity;
+use crate::data::json::JsonValue;
 use crate::data::value::{DataValue, JsonData, UuidWrapper, Validity, ValidityTs, Vector};
+use crate::parse::parse_type;
 use crate::Num;
 
-#[derive(Debug, Clone, Eq, PartialEq, serde_derive::Deserialize, serde_derive::Serialize)]
+/// Type of a column, as written in the schema of a stored relation
+#[derive(Debug, Clone, Eq, PartialEq, Hash, serde_derive::Deserialize, serde_derive::Serialize)]
 pub struct NullableColType {
+    /// the type proper
     pub coltype: ColType,
+    /// whether `null` is accepted
     pub nullable: bool,
 }
 
@@ -37,7 +43,30 @@ impl Display for NullableColType {
             ColType::Bool => f.write_str("Bool")?,
             ColType::Int => f.write_str("Int")?,
             ColType::Float => f.write_str("Float")?,
+            ColType::Float32 => f.write_str("Float32")?,
             ColType::String => f.write_str("String")?,
+            ColType::BoundedString { max_len } => write!(f, "String({max_len})")?,
+            ColType::Union(members) => {
+                for (i, member) in members.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(" | ")?;
+                    }
+                    let member = NullableColType {
+                        coltype: member.clone(),
+                        nullable: false,
+                    };
+                    write!(f, "{member}")?;
+                }
+            }
+            ColType::Quantity { base, unit } => {
+                let base = NullableColType {
+                    coltype: (**base).clone(),
+                    nullable: false,
+                };
+                // quoted strings in types take the escapes of JSON strings
+                let unit = JsonValue::String(unit.clone());
+                write!(f, "{base}<{unit}>")?
+            }
             ColType::Bytes => f.write_str("Bytes")?,
             ColType::Uuid => f.write_str("Uuid")?,
             ColType::Validity => f.write_str("Validity")?,
@@ -49,6 +78,21 @@ impl Display for NullableColType {
                 }
                 f.write_str("]")?;
             }
+            ColType::BoundedList {
+                eltype,
+                min_len,
+                max_len,
+            } => {
+                write!(f, "[{eltype};")?;
+                if *min_len != 0 {
+                    write!(f, "{min_len}")?;
+                }
+                f.write_str("..")?;
+                if let Some(max_len) = max_len {
+                    write!(f, "{max_len}")?;
+                }
+                f.write_str("]")?;
+            }
             ColType::Tuple(t) => {
                 f.write_str("(")?;
                 let l = t.len();
@@ -80,33 +124,78 @@ impl Display for NullableColType {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, serde_derive::Deserialize, serde_derive::Serialize)]
+/// Column type without nullability
+#[derive(Debug, Clone, Eq, PartialEq, Hash, serde_derive::Deserialize, serde_derive::Serialize)]
 pub enum ColType {
+    /// any value
     Any,
+    /// boolean
     Bool,
+    /// integer
     Int,
+    /// float, with double precision
     Float,
+    /// float, rounded to single precision
+    Float32,
+    /// string
     String,
+    /// string of at most this many chars
+    BoundedString {
+        /// maximum number of chars
+        max_len: usize,
+    },
+    /// bytes, also accepting base64 strings unless [CoerceOptions::decode_base64] is off
     Bytes,
+    /// UUID
     Uuid,
+    /// list with elements of the same type
     List {
+        /// type of the elements
         eltype: Box<NullableColType>,
+        /// required length, if any
         len: Option<usize>,
     },
+    /// vector for proximity search
     Vec {
+        /// type of the elements
         eltype: VecElementType,
+        /// length of the vector
         len: usize,
     },
+    /// list with elements of the same type and a length within bounds, both inclusive
+    BoundedList {
+        /// type of the elements
+        eltype: Box<NullableColType>,
+        /// minimum length
+        min_len: usize,
+        /// maximum length, if any
+        max_len: Option<usize>,
+    },
+    /// list with a fixed type for each position
     Tuple(Vec<NullableColType>),
+    /// validity for time travel
     Validity,
+    /// Json
     Json,
+    /// value of any of these types, which are tried in order
+    Union(Vec<ColType>),
+    /// number with a unit attached, the unit is only a label and is not checked
+    Quantity {
+        /// numeric type of the values
+        base: Box<ColType>,
+        /// unit label
+        unit: String,
+    },
 }
 
+/// Element type of vectors
 #[derive(
     Debug, Copy, Clone, Eq, PartialEq, Hash, serde_derive::Deserialize, serde_derive::Serialize,
 )]
 pub enum VecElementType {
+    /// 32-bit float
     F32,
+    /// 64-bit float
     F64,
 }
 
@@ -170,8 +259,490 @@ impl StoredRelationMetadata {
     }
 }
 
+/// Options relaxing [NullableColType::coerce_with]. The defaults are strict, except that
+/// strings are decoded as base64 for `Bytes`, which is how bytes are carried in JSON.
+#[derive(Debug, Clone)]
+pub struct CoerceOptions {
+    /// Drop trailing elements of a list coerced into a tuple type instead of erroring.
+    /// Lists shorter than the tuple are still rejected.
+    pub allow_extra_tuple_elems: bool,
+    /// Accept the strings in `truthy_strings` and `falsy_strings` when coercing into `Bool`.
+    /// Other strings are still rejected.
+    pub lenient_bool: bool,
+    /// Strings read as `true` under `lenient_bool`, compared case-insensitively
+    pub truthy_strings: Vec<SmartString<LazyCompact>>,
+    /// Strings read as `false` under `lenient_bool`, compared case-insensitively
+    pub falsy_strings: Vec<SmartString<LazyCompact>>,
+    /// Decode strings as base64 when coercing into `Bytes`, erroring on invalid base64.
+    /// On by default, as `Bytes` columns have always taken base64 strings, so that any
+    /// string that happens to be valid base64 is stored as the bytes it decodes to.
+    /// Turn it off to accept only actual bytes.
+    pub decode_base64: bool,
+    /// Coerce into the member of a union needing the least coercion instead of the first
+    /// member accepting the value: a member the value already belongs to is preferred over
+    /// one it is converted to within its kind, such as an Int widened to a Float, which is
+    /// preferred over one it is parsed into, such as a string into a Uuid. Ties go to the
+    /// member declared first.
+    pub prefer_narrowest_union: bool,
+}
+
+impl Default for CoerceOptions {
+    fn default() -> Self {
+        Self {
+            allow_extra_tuple_elems: false,
+            lenient_bool: false,
+            truthy_strings: vec!["true".into(), "yes".into()],
+            falsy_strings: vec!["false".into(), "no".into()],
+            decode_base64: true,
+            prefer_narrowest_union: false,
+        }
+    }
+}
+
 impl NullableColType {
+    /// The union of `members`, accepting a value if any of them does, trying them in order.
+    /// Nested unions are flattened and duplicates dropped, a union containing `Any` is `Any`,
+    /// and a union of a single type is that type. It is nullable if any member is.
+    pub fn union(members: impl IntoIterator<Item = NullableColType>) -> NullableColType {
+        let mut nullable = false;
+        let mut flattened: Vec<ColType> = vec![];
+        for member in members {
+            nullable |= member.nullable;
+            let coltypes = match member.coltype {
+                ColType::Union(nested) => nested,
+                coltype => vec![coltype],
+            };
+            for coltype in coltypes {
+                if !flattened.contains(&coltype) {
+                    flattened.push(coltype);
+                }
+            }
+        }
+        let coltype = if flattened.is_empty() || flattened.contains(&ColType::Any) {
+            ColType::Any
+        } else if flattened.len() == 1 {
+            flattened.pop().unwrap()
+        } else {
+            ColType::Union(flattened)
+        };
+        NullableColType { coltype, nullable }
+    }
+    /// Returns this type in its simplest form, e.g. for display: unions are normalized as
+    /// by [NullableColType::union], so `Int | Any` is `Any`, and so are the types of the
+    /// elements of Lists and Tuples and the base of a Quantity. `[Any]` stays as it is.
+    /// Nullability is a single flag, so `??Int` can only be built as a nullable union of
+    /// the single member `Int`, which is simplified to `?Int`.
+    pub fn simplify(self) -> NullableColType {
+        let nullable = self.nullable;
+        let coltype = match self.coltype {
+            ColType::Union(members) => {
+                NullableColType::union(members.into_iter().map(|member| {
+                    NullableColType {
+                        coltype: member,
+                        nullable: false,
+                    }
+                    .simplify()
+                }))
+                .coltype
+            }
+            ColType::List { eltype, len } => ColType::List {
+                eltype: Box::new(eltype.simplify()),
+                len,
+            },
+            ColType::BoundedList {
+                eltype,
+                min_len,
+                max_len,
+            } => ColType::BoundedList {
+                eltype: Box::new(eltype.simplify()),
+                min_len,
+                max_len,
+            },
+            ColType::Tuple(typ) => {
+                ColType::Tuple(typ.into_iter().map(NullableColType::simplify).collect())
+            }
+            ColType::Quantity { base, unit } => ColType::Quantity {
+                base: Box::new(
+                    NullableColType {
+                        coltype: *base,
+                        nullable: false,
+                    }
+                    .simplify()
+                    .coltype,
+                ),
+                unit,
+            },
+            coltype => coltype,
+        };
+        NullableColType { coltype, nullable }
+    }
+    /// Returns this type with `null` accepted. Types that are already nullable are unchanged.
+    pub fn into_nullable(self) -> Self {
+        Self {
+            nullable: true,
+            ..self
+        }
+    }
+    /// Returns this type with `null` rejected. Types that are not nullable are unchanged.
+    pub fn into_required(self) -> Self {
+        Self {
+            nullable: false,
+            ..self
+        }
+    }
+    /// Whether every value of this type also fits `other`, without looking at any value.
+    /// `Any` accepts everything, Ints are assignable to Floats, non-null types to their
+    /// nullable counterparts, and Lists and Tuples are compared element by element.
+    /// Units of `Quantity` types are ignored.
+    pub fn is_assignable_to(&self, other: &NullableColType) -> bool {
+        (!self.nullable || other.nullable) && coltype_assignable(&self.coltype, &other.coltype)
+    }
+    /// The narrowest type that both this type and `other` are assignable to, e.g. for
+    /// the columns of two rules feeding the same relation. It is nullable if either is.
+    /// Lists and Tuples are unified element by element, and types that are otherwise
+    /// unrelated are unified into their union.
+    pub fn unify(&self, other: &NullableColType) -> NullableColType {
+        let nullable = self.nullable || other.nullable;
+        let coltype = match (&self.coltype, &other.coltype) {
+            (a, b) if coltype_assignable(a, b) => b.clone(),
+            (a, b) if coltype_assignable(b, a) => a.clone(),
+            (ColType::List { eltype: a, len: la }, ColType::List { eltype: b, len: lb })
+                if la == lb =>
+            {
+                ColType::List {
+                    eltype: Box::new(a.unify(b)),
+                    len: *la,
+                }
+            }
+            (ColType::Tuple(a), ColType::Tuple(b)) if a.len() == b.len() => {
+                ColType::Tuple(a.iter().zip(b).map(|(a, b)| a.unify(b)).collect())
+            }
+            (a, b) => {
+                return NullableColType::union([
+                    NullableColType {
+                        coltype: a.clone(),
+                        nullable,
+                    },
+                    NullableColType {
+                        coltype: b.clone(),
+                        nullable,
+                    },
+                ])
+            }
+        };
+        NullableColType { coltype, nullable }
+    }
+    /// A canonical zero value of this type, e.g. for filling in missing columns:
+    /// `null` for nullable types, `false`, `0`, `0.0`, empty strings, bytes and Lists,
+    /// zero vectors and JSON `null`. Tuples and fixed-length Lists are filled with the
+    /// defaults of their elements. Returns `None` for `Any`, `Uuid` and `Validity`,
+    /// or for composites of them, which have no sensible default.
+    pub fn default_value(&self) -> Option<DataValue> {
+        if self.nullable {
+            return Some(DataValue::Null);
+        }
+        Some(match &self.coltype {
+            ColType::Any | ColType::Uuid | ColType::Validity => return None,
+            ColType::Bool => DataValue::from(false),
+            ColType::Int => DataValue::from(0),
+            ColType::Float | ColType::Float32 => DataValue::from(0.0),
+            ColType::String | ColType::BoundedString { .. } => DataValue::from(""),
+            ColType::Bytes => DataValue::Bytes(vec![]),
+            ColType::Json => DataValue::Json(JsonData(JsonValue::Null)),
+            ColType::Quantity { base, .. } => NullableColType {
+                coltype: (**base).clone(),
+                nullable: false,
+            }
+            .default_value()?,
+            ColType::Union(members) => NullableColType {
+                coltype: members[0].clone(),
+                nullable: false,
+            }
+            .default_value()?,
+            ColType::List { len: None, .. } => DataValue::List(vec![]),
+            ColType::List {
+                eltype,
+                len: Some(n),
+            } => DataValue::List(vec![eltype.default_value()?; *n]),
+            ColType::BoundedList {
+                eltype, min_len, ..
+            } => DataValue::List(vec![eltype.default_value()?; *min_len]),
+            ColType::Vec { eltype, len } => DataValue::Vec(match eltype {
+                VecElementType::F32 => Vector::F32(ndarray::Array1::zeros(*len)),
+                VecElementType::F64 => Vector::F64(ndarray::Array1::zeros(*len)),
+            }),
+            ColType::Tuple(typ) => DataValue::List(
+                typ.iter()
+                    .map(|t| t.default_value())
+                    .collect::<Option<_>>()?,
+            ),
+        })
+    }
+    /// The unit label of a `Quantity` type, `None` for other types.
+    pub fn unit(&self) -> Option<&str> {
+        match &self.coltype {
+            ColType::Quantity { unit, .. } => Some(unit),
+            _ => None,
+        }
+    }
+    /// Column type for SQL DDL, in the PostgreSQL dialect. Non-nullable types get
+    /// `NOT NULL`, and types with no SQL counterpart, including all composites, are `JSONB`.
+    pub fn to_sql_type(&self) -> String {
+        let base = match &self.coltype {
+            ColType::Bool => "BOOLEAN".to_string(),
+            ColType::Int => "BIGINT".to_string(),
+            ColType::Float => "DOUBLE PRECISION".to_string(),
+            ColType::Float32 => "REAL".to_string(),
+            ColType::String => "TEXT".to_string(),
+            ColType::BoundedString { max_len } => format!("VARCHAR({max_len})"),
+            ColType::Bytes => "BYTEA".to_string(),
+            ColType::Uuid => "UUID".to_string(),
+            ColType::Quantity { base, .. } => NullableColType {
+                coltype: (**base).clone(),
+                nullable: true,
+            }
+            .to_sql_type(),
+            ColType::Any
+            | ColType::List { .. }
+            | ColType::BoundedList { .. }
+            | ColType::Vec { .. }
+            | ColType::Tuple(_)
+            | ColType::Union(_)
+            | ColType::Validity
+            | ColType::Json => "JSONB".to_string(),
+        };
+        if self.nullable {
+            base
+        } else {
+            format!("{base} NOT NULL")
+        }
+    }
+    /// Coerces a JSON value into this type and converts the result back into JSON.
+    /// Errors name the path of the offending element, e.g. `at [2][1]: ...`.
+    pub fn cast_json(&self, input: &JsonValue) -> Result<JsonValue> {
+        Ok(JsonValue::from(self.coerce_json(input)?))
+    }
+    pub(crate) fn coerce_json(&self, input: &JsonValue) -> Result<DataValue> {
+        self.cast_json_at(input, current_validity())
+    }
+    /// Coerces a JSON value into this type like [NullableColType::cast_json], also returning
+    /// the type of the result. For `Any` that is the narrowest type inferred from the value,
+    /// otherwise it is this type.
+    pub fn coerce_json_inferred(&self, input: &JsonValue) -> Result<(DataValue, NullableColType)> {
+        let coerced = self.coerce_json(input)?;
+        let typ = if self.coltype == ColType::Any {
+            coerced.inferred_typing()
+        } else {
+            self.clone()
+        };
+        Ok((coerced, typ))
+    }
+    /// Infers a type that fits all of `samples`, also returning a note for each place
+    /// where the samples disagree. Ints and Floats widen to Float, other disagreements
+    /// widen to Any. Nulls make the type nullable without a note. Lists are inferred as
+    /// Tuples and compared element by element, which makes this suitable for rows.
+    /// Tuples of different lengths merge into a List.
+    pub fn infer_report(samples: &[DataValue]) -> (NullableColType, Vec<String>) {
+        let mut widened = BTreeSet::new();
+        let mut notes = vec![];
+        let typ = samples
+            .iter()
+            .map(infer_positional)
+            .reduce(|acc, t| merge_inferred(acc, t, "", &mut widened, &mut notes))
+            .unwrap_or(NullableColType {
+                coltype: ColType::Any,
+                nullable: true,
+            });
+        (typ, notes)
+    }
+    /// Coerces a value into this type like [NullableColType::coerce], but instead of
+    /// stopping at the first element of a List or Tuple that fails, reports all of them.
+    /// Each error names the path of its element, e.g. `at [2][1]: ...`.
+    pub fn coerce_collecting(&self, data: DataValue) -> Result<DataValue, Vec<Report>> {
+        let mut errors = vec![];
+        let coerced = self.coerce_collecting_at(data, current_validity(), &mut errors);
+        match coerced {
+            Some(v) if errors.is_empty() => Ok(v),
+            _ => Err(errors),
+        }
+    }
+    fn coerce_collecting_at(
+        &self,
+        data: DataValue,
+        cur_vld: ValidityTs,
+        errors: &mut Vec<Report>,
+    ) -> Option<DataValue> {
+        let eltypes = match (&self.coltype, &data) {
+            (ColType::List { eltype, len }, DataValue::List(l))
+                if len.is_none() || *len == Some(l.len()) =>
+            {
+                vec![eltype.as_ref(); l.len()]
+            }
+            (
+                ColType::BoundedList {
+                    eltype,
+                    min_len,
+                    max_len,
+                },
+                DataValue::List(l),
+            ) if len_within(l.len(), *min_len, *max_len) => {
+                vec![eltype.as_ref(); l.len()]
+            }
+            (ColType::Tuple(typ), DataValue::List(l)) if typ.len() == l.len() => {
+                typ.iter().collect()
+            }
+            _ => {
+                return match self.coerce(data, cur_vld) {
+                    Ok(v) => Some(v),
+                    Err(err) => {
+                        errors.push(err);
+                        None
+                    }
+                };
+            }
+        };
+        let l = match data {
+            DataValue::List(l) => l,
+            _ => unreachable!(),
+        };
+        let mut coerced = Vec::with_capacity(l.len());
+        for (i, (el, typ)) in l.into_iter().zip(eltypes).enumerate() {
+            let mut el_errors = vec![];
+            coerced.push(typ.coerce_collecting_at(el, cur_vld, &mut el_errors));
+            errors.extend(el_errors.into_iter().map(|err| nested_at(err, i)));
+        }
+        Some(DataValue::List(coerced.into_iter().collect::<Option<_>>()?))
+    }
+    /// Coerces each value of `row` into the type at the same position in `types`.
+    /// Errors if the lengths differ, or name the index of the first column that fails.
+    pub fn coerce_row(types: &[NullableColType], row: Vec<DataValue>) -> Result<Vec<DataValue>> {
+        #[derive(Debug, Error, Diagnostic)]
+        #[error("row has {1} columns but {0} types were given")]
+        #[diagnostic(code(eval::coercion_row_arity))]
+        struct RowArityMismatch(usize, usize);
+
+        ensure!(
+            types.len() == row.len(),
+            RowArityMismatch(types.len(), row.len())
+        );
+        let cur_vld = current_validity();
+        types
+            .iter()
+            .zip(row)
+            .enumerate()
+            .map(|(i, (typ, v))| {
+                typ.coerce(v, cur_vld)
+                    .wrap_err_with(|| format!("at column {i}"))
+            })
+            .collect()
+    }
+    fn cast_json_at(&self, input: &JsonValue, cur_vld: ValidityTs) -> Result<DataValue> {
+        let eltypes = match (&self.coltype, input) {
+            (ColType::List { eltype, len }, JsonValue::Array(arr))
+                if len.is_none() || *len == Some(arr.len()) =>
+            {
+                vec![eltype.as_ref(); arr.len()]
+            }
+            (
+                ColType::BoundedList {
+                    eltype,
+                    min_len,
+                    max_len,
+                },
+                JsonValue::Array(arr),
+            ) if len_within(arr.len(), *min_len, *max_len) => {
+                vec![eltype.as_ref(); arr.len()]
+            }
+            (ColType::Tuple(typ), JsonValue::Array(arr)) if typ.len() == arr.len() => {
+                typ.iter().collect()
+            }
+            _ => return self.coerce(DataValue::from(input), cur_vld),
+        };
+        let JsonValue::Array(arr) = input else {
+            unreachable!()
+        };
+        let mut coerced = Vec::with_capacity(arr.len());
+        for (i, (el, typ)) in arr.iter().zip(eltypes).enumerate() {
+            coerced.push(
+                typ.cast_json_at(el, cur_vld)
+                    .map_err(|err| nested_at(err, i))?,
+            );
+        }
+        Ok(DataValue::List(coerced))
+    }
     pub(crate) fn coerce(&self, data: DataValue, cur_vld: ValidityTs) -> Result<DataValue> {
+        self.coerce_with(data, cur_vld, &CoerceOptions::default())
+    }
+    /// Coerces like [NullableColType::coerce_with], also returning the index of the union
+    /// member that took the value. The index is `None` for a null taken by a nullable union
+    /// itself, and a type that is not a union counts as its own only member.
+    pub fn coerce_union(
+        &self,
+        data: DataValue,
+        cur_vld: ValidityTs,
+        opts: &CoerceOptions,
+    ) -> Result<(Option<usize>, DataValue)> {
+        if matches!(data, DataValue::Null) {
+            return Ok((None, self.coerce_with(data, cur_vld, opts)?));
+        }
+        match &self.coltype {
+            ColType::Union(members) => {
+                let (i, coerced) = self.coerce_into_member(members, data, cur_vld, opts)?;
+                Ok((Some(i), coerced))
+            }
+            _ => Ok((Some(0), self.coerce_with(data, cur_vld, opts)?)),
+        }
+    }
+    fn coerce_into_member(
+        &self,
+        members: &[ColType],
+        data: DataValue,
+        cur_vld: ValidityTs,
+        opts: &CoerceOptions,
+    ) -> Result<(usize, DataValue)> {
+        #[derive(Debug, Error, Diagnostic)]
+        #[error("data coercion failed: no member of type {0} accepts value {1:?}")]
+        #[diagnostic(code(eval::coercion_no_union_member))]
+        struct NoUnionMemberMatches(NullableColType, DataValue, #[related] Vec<Report>);
+
+        let mut errs = vec![];
+        let mut best: Option<(u8, usize, DataValue)> = None;
+        for (i, member) in members.iter().enumerate() {
+            let member = NullableColType {
+                coltype: member.clone(),
+                nullable: false,
+            };
+            match member.coerce_with(data.clone(), cur_vld, opts) {
+                Ok(coerced) => {
+                    if !opts.prefer_narrowest_union {
+                        return Ok((i, coerced));
+                    }
+                    let cost = coercion_cost(&data, &coerced);
+                    let better = match &best {
+                        None => true,
+                        Some((best_cost, _, _)) => cost < *best_cost,
+                    };
+                    if better {
+                        best = Some((cost, i, coerced));
+                    }
+                }
+                Err(err) => errs.push(err.wrap_err(format!("not accepted by member {member}"))),
+            }
+        }
+        match best {
+            Some((_, i, coerced)) => Ok((i, coerced)),
+            None => bail!(NoUnionMemberMatches(self.clone(), data, errs)),
+        }
+    }
+    /// Coerces `data` into this type like inserting it into a column of this type would,
+    /// relaxed by `opts`. `cur_vld` is the timestamp to use for `Validity` values given as
+    /// the string `"ASSERT"` or `"RETRACT"`.
+    pub fn coerce_with(
+        &self,
+        data: DataValue,
+        cur_vld: ValidityTs,
+        opts: &CoerceOptions,
+    ) -> Result<DataValue> {
         if matches!(data, DataValue::Null) {
             return if self.nullable {
                 Ok(data)
@@ -210,9 +781,73 @@ impl NullableColType {
                 }
                 d => d,
             },
-            ColType::Bool => DataValue::from(data.get_bool().ok_or_else(make_err)?),
-            ColType::Int => DataValue::from(data.get_int().ok_or_else(make_err)?),
-            ColType::Float => DataValue::from(data.get_float().ok_or_else(make_err)?),
+            ColType::Bool => match &data {
+                DataValue::Str(s) if opts.lenient_bool => {
+                    let matches = |candidates: &[SmartString<LazyCompact>]| {
+                        candidates.iter().any(|c| c.eq_ignore_ascii_case(s))
+                    };
+                    if matches(&opts.truthy_strings) {
+                        DataValue::Bool(true)
+                    } else if matches(&opts.falsy_strings) {
+                        DataValue::Bool(false)
+                    } else {
+                        bail!(make_err())
+                    }
+                }
+                _ => DataValue::from(data.get_bool().ok_or_else(make_err)?),
+            },
+            ColType::Int => {
+                #[derive(Debug, Error, Diagnostic)]
+                #[error("data coercion failed: {0} is out of range for type Int")]
+                #[diagnostic(code(eval::coercion_int_out_of_range))]
+                #[diagnostic(help("Use a Float column for numbers this large"))]
+                struct IntOutOfRange(DataValue);
+
+                match &data {
+                    DataValue::Num(Num::Float(f))
+                        if f.round() == *f && !(i64::MIN as f64..i64::MAX as f64).contains(f) =>
+                    {
+                        bail!(IntOutOfRange(data))
+                    }
+                    // e.g. a number taken out of a Json value
+                    DataValue::Json(JsonData(JsonValue::Number(n))) => match n.as_i64() {
+                        Some(i) => DataValue::from(i),
+                        None if n.is_u64() => bail!(IntOutOfRange(data)),
+                        None => bail!(make_err()),
+                    },
+                    _ => DataValue::from(data.get_int().ok_or_else(make_err)?),
+                }
+            }
+            ColType::Float => {
+                match data {
+                    DataValue::Num(Num::Int(i)) => {
+                        let f = i as f64;
+                        if f as i128 != i as i128 {
+                            #[derive(Debug, Error, Diagnostic)]
+                            #[error("data coercion failed: {0} cannot be represented exactly as a Float")]
+                            #[diagnostic(code(eval::coercion_int_not_exact_float))]
+                            struct IntNotExactFloat(i64);
+
+                            bail!(IntNotExactFloat(i))
+                        }
+                        DataValue::from(f)
+                    }
+                    _ => DataValue::from(data.get_float().ok_or_else(make_err)?),
+                }
+            }
+            ColType::Float32 => {
+                let f = data.get_float().ok_or_else(make_err)?;
+                let rounded = f as f32;
+                if f.is_finite() && rounded.is_infinite() {
+                    #[derive(Debug, Error, Diagnostic)]
+                    #[error("data coercion failed: {0} is out of range for type Float32")]
+                    #[diagnostic(code(eval::coercion_f32_overflow))]
+                    struct Float32Overflow(f64);
+
+                    bail!(Float32Overflow(f))
+                }
+                DataValue::from(rounded as f64)
+            }
             ColType::String => {
                 if matches!(data, DataValue::Str(_)) {
                     data
@@ -220,9 +855,30 @@ impl NullableColType {
                     bail!(make_err())
                 }
             }
+            ColType::Quantity { base, .. } => NullableColType {
+                coltype: (**base).clone(),
+                nullable: false,
+            }
+            .coerce_with(data, cur_vld, opts)?,
+            ColType::Union(members) => self.coerce_into_member(members, data, cur_vld, opts)?.1,
+            ColType::BoundedString { max_len } => match &data {
+                DataValue::Str(s) => {
+                    let len = s.chars().count();
+                    if len > *max_len {
+                        #[derive(Debug, Error, Diagnostic)]
+                        #[error("data coercion failed: string of {1} chars is longer than allowed by type {0}")]
+                        #[diagnostic(code(eval::coercion_string_too_long))]
+                        struct StringTooLong(NullableColType, usize);
+
+                        bail!(StringTooLong(self.clone(), len))
+                    }
+                    data
+                }
+                _ => bail!(make_err()),
+            },
             ColType::Bytes => match data {
                 d @ DataValue::Bytes(_) => d,
-                DataValue::Str(s) => {
+                DataValue::Str(s) if opts.decode_base64 => {
                     #[derive(Debug, Error, Diagnostic)]
                     #[error("cannot decode string as base64-encoded bytes: {0}")]
                     #[diagnostic(code(eval::coercion_bad_base_64))]
@@ -242,7 +898,36 @@ impl NullableColType {
                     }
                     DataValue::List(
                         l.into_iter()
-                            .map(|el| eltype.coerce(el, cur_vld))
+                            .enumerate()
+                            .map(|(i, el)| {
+                                eltype
+                                    .coerce_with(el, cur_vld, opts)
+                                    .map_err(|err| nested_at(err, i))
+                            })
+                            .try_collect()?,
+                    )
+                } else {
+                    bail!(make_err())
+                }
+            }
+            ColType::BoundedList {
+                eltype,
+                min_len,
+                max_len,
+            } => {
+                if let DataValue::List(l) = data {
+                    ensure!(
+                        len_within(l.len(), *min_len, *max_len),
+                        BadListLength(self.clone(), l.len())
+                    );
+                    DataValue::List(
+                        l.into_iter()
+                            .enumerate()
+                            .map(|(i, el)| {
+                                eltype
+                                    .coerce_with(el, cur_vld, opts)
+                                    .map_err(|err| nested_at(err, i))
+                            })
                             .try_collect()?,
                     )
                 } else {
@@ -318,12 +1003,19 @@ impl NullableColType {
                 _ => bail!(make_err()),
             },
             ColType::Tuple(typ) => {
-                if let DataValue::List(l) = data {
+                if let DataValue::List(mut l) = data {
+                    if opts.allow_extra_tuple_elems {
+                        l.truncate(typ.len());
+                    }
                     ensure!(typ.len() == l.len(), BadListLength(self.clone(), l.len()));
                     DataValue::List(
                         l.into_iter()
                             .zip(typ.iter())
-                            .map(|(el, t)| t.coerce(el, cur_vld))
+                            .enumerate()
+                            .map(|(i, (el, t))| {
+                                t.coerce_with(el, cur_vld, opts)
+                                    .map_err(|err| nested_at(err, i))
+                            })
                             .try_collect()?,
                     )
                 } else {
@@ -352,11 +1044,8 @@ impl NullableColType {
                                 None => (true, s),
                                 Some(remaining) => (false, remaining),
                             };
-                            let dt = DateTime::parse_from_rfc3339(ts_str)
-                                .map_err(|_| InvalidValidity(DataValue::Str(s.into())))?;
-                            let st: SystemTime = dt.into();
-                            let microseconds =
-                                st.duration_since(UNIX_EPOCH).unwrap().as_micros() as i64;
+                            let microseconds = parse_rfc3339_micros(ts_str)
+                                .ok_or_else(|| InvalidValidity(DataValue::Str(s.into())))?;
 
                             if microseconds == i64::MAX || microseconds == i64::MIN {
                                 bail!(InvalidValidity(DataValue::Str(s.into())))
@@ -370,7 +1059,10 @@ impl NullableColType {
                     },
                     DataValue::List(l) => {
                         if l.len() == 2 {
-                            let o_ts = l[0].get_int();
+                            let o_ts = match &l[0] {
+                                DataValue::Str(s) => parse_rfc3339_micros(s),
+                                v => v.get_int(),
+                            };
                             let o_is_assert = l[1].get_bool();
                             if let (Some(ts), Some(is_assert)) = (o_ts, o_is_assert) {
                                 if ts == i64::MAX || ts == i64::MIN {
@@ -417,14 +1109,14 @@ impl NullableColType {
                 DataValue::List(l) => {
                     let mut arr = Vec::with_capacity(l.len());
                     for el in l {
-                        arr.push(self.coerce(el, cur_vld)?);
+                        arr.push(self.coerce_with(el, cur_vld, opts)?);
                     }
                     arr.into()
                 }
                 DataValue::Set(l) => {
                     let mut arr = Vec::with_capacity(l.len());
                     for el in l {
-                        arr.push(self.coerce(el, cur_vld)?);
+                        arr.push(self.coerce_with(el, cur_vld, opts)?);
                     }
                     arr.into()
                 }
@@ -455,3 +1147,288 @@ impl NullableColType {
         })
     }
 }
+
+/// Parsed column types keyed by their source text, so that coercing against
+/// the same type string many times only parses it once.
+#[derive(Debug, Default)]
+pub struct TypingCache {
+    types: BTreeMap<String, NullableColType>,
+}
+
+impl TypingCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns the parsed type for `type_str`, parsing and caching it on first use.
+    /// Strings that fail to parse are not cached.
+    pub fn get_or_parse(&mut self, type_str: &str) -> Result<&NullableColType> {
+        if !self.types.contains_key(type_str) {
+            let typ = parse_type(type_str)?;
+            self.types.insert(type_str.to_string(), typ);
+        }
+        Ok(&self.types[type_str])
+    }
+    /// Coerces `value` into the type written as `type_str`.
+    pub fn coerce(&mut self, type_str: &str, value: DataValue) -> Result<DataValue> {
+        self.get_or_parse(type_str)?
+            .coerce(value, current_validity())
+    }
+    /// Number of cached types.
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+    /// Returns `true` if nothing has been cached yet.
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+}
+
+impl DataValue {
+    /// Narrowest type that this value coerces into unchanged, e.g. for error messages.
+    /// Null is `Any?`. Lists whose non-null elements share a type are homogeneous Lists,
+    /// other Lists are Tuples, and Sets are inferred like Lists.
+    pub fn inferred_typing(&self) -> NullableColType {
+        let required = |coltype| NullableColType {
+            coltype,
+            nullable: false,
+        };
+        match self {
+            DataValue::Null => NullableColType {
+                coltype: ColType::Any,
+                nullable: true,
+            },
+            DataValue::Bool(_) => required(ColType::Bool),
+            DataValue::Num(Num::Int(_)) => required(ColType::Int),
+            DataValue::Num(Num::Float(_)) => required(ColType::Float),
+            DataValue::Str(_) => required(ColType::String),
+            DataValue::Bytes(_) => required(ColType::Bytes),
+            DataValue::Uuid(_) => required(ColType::Uuid),
+            DataValue::Validity(_) => required(ColType::Validity),
+            DataValue::Json(_) => required(ColType::Json),
+            DataValue::Vec(v) => required(ColType::Vec {
+                eltype: match v {
+                    Vector::F32(_) => VecElementType::F32,
+                    Vector::F64(_) => VecElementType::F64,
+                },
+                len: v.len(),
+            }),
+            DataValue::List(l) => {
+                let eltypes = l.iter().map(DataValue::inferred_typing).collect_vec();
+                let mut non_null = eltypes.iter().filter(|t| t.coltype != ColType::Any);
+                let shared = match non_null.next() {
+                    None => Some(ColType::Any),
+                    Some(first) if non_null.all(|t| *t == *first) => Some(first.coltype.clone()),
+                    _ => None,
+                };
+                match shared {
+                    Some(coltype) => required(ColType::List {
+                        eltype: Box::new(NullableColType {
+                            coltype,
+                            nullable: eltypes.iter().any(|t| t.nullable),
+                        }),
+                        len: None,
+                    }),
+                    None => required(ColType::Tuple(eltypes)),
+                }
+            }
+            DataValue::Set(s) => DataValue::List(s.iter().cloned().collect()).inferred_typing(),
+            DataValue::Regex(_) | DataValue::Bot => required(ColType::Any),
+        }
+    }
+}
+
+/// Microseconds since the UNIX epoch of an RFC 3339 timestamp
+fn parse_rfc3339_micros(s: &str) -> Option<i64> {
+    let dt = DateTime::parse_from_rfc3339(s).ok()?;
+    dt.timestamp()
+        .checked_mul(1_000_000)?
+        .checked_add(dt.timestamp_subsec_micros() as i64)
+}
+
+/// Infers the type of a value like [DataValue::inferred_typing], but with Lists as Tuples
+fn infer_positional(v: &DataValue) -> NullableColType {
+    match v {
+        DataValue::List(l) => NullableColType {
+            coltype: ColType::Tuple(l.iter().map(infer_positional).collect()),
+            nullable: false,
+        },
+        v => v.inferred_typing(),
+    }
+}
+
+/// Merges two inferred types for the value at `path`. `Any?` is what a null infers to,
+/// and is narrowed by the other type unless it is the result of an earlier widening,
+/// whose paths are kept in `widened`.
+fn merge_inferred(
+    a: NullableColType,
+    b: NullableColType,
+    path: &str,
+    widened: &mut BTreeSet<String>,
+    notes: &mut Vec<String>,
+) -> NullableColType {
+    let is_null = |t: &NullableColType| t.coltype == ColType::Any && t.nullable;
+    let nullable = a.nullable || b.nullable;
+    let coltype = if a.coltype == b.coltype || is_null(&b) {
+        a.coltype
+    } else if is_null(&a) && !widened.contains(path) {
+        b.coltype
+    } else {
+        let name = |coltype: &ColType| NullableColType {
+            coltype: coltype.clone(),
+            nullable: false,
+        };
+        let location = if path.is_empty() {
+            "value".to_string()
+        } else {
+            format!("value at {path}")
+        };
+        match (a.coltype, b.coltype) {
+            (ColType::Tuple(x), ColType::Tuple(y)) if x.len() == y.len() => ColType::Tuple(
+                x.into_iter()
+                    .zip(y)
+                    .enumerate()
+                    .map(|(i, (x, y))| {
+                        merge_inferred(x, y, &format!("{path}[{i}]"), widened, notes)
+                    })
+                    .collect(),
+            ),
+            (
+                x @ (ColType::Tuple(_) | ColType::List { len: None, .. }),
+                y @ (ColType::Tuple(_) | ColType::List { len: None, .. }),
+            ) => {
+                let elements = |coltype| match coltype {
+                    ColType::Tuple(t) => t,
+                    ColType::List { eltype, .. } => vec![*eltype],
+                    _ => unreachable!(),
+                };
+                let elpath = format!("{path}[]");
+                let eltype = elements(x)
+                    .into_iter()
+                    .chain(elements(y))
+                    .reduce(|acc, t| merge_inferred(acc, t, &elpath, widened, notes))
+                    .unwrap();
+                ColType::List {
+                    eltype: Box::new(eltype),
+                    len: None,
+                }
+            }
+            (ColType::Int, ColType::Float) | (ColType::Float, ColType::Int) => {
+                let note = format!("{location} saw both Int and Float, widened to Float");
+                if !notes.contains(&note) {
+                    notes.push(note);
+                }
+                ColType::Float
+            }
+            (x, y) => {
+                if widened.insert(path.to_string()) {
+                    notes.push(format!(
+                        "{location} saw both {} and {}, widened to Any",
+                        name(&x),
+                        name(&y)
+                    ));
+                }
+                ColType::Any
+            }
+        }
+    };
+    NullableColType { coltype, nullable }
+}
+
+fn coltype_assignable(from: &ColType, to: &ColType) -> bool {
+    match (from, to) {
+        (_, ColType::Any) => true,
+        (ColType::Union(members), to) => members.iter().all(|m| coltype_assignable(m, to)),
+        (from, ColType::Union(members)) => members.iter().any(|m| coltype_assignable(from, m)),
+        (ColType::Quantity { base, .. }, to) => coltype_assignable(base, to),
+        (from, ColType::Quantity { base, .. }) => coltype_assignable(from, base),
+        (ColType::Int, ColType::Float | ColType::Float32) | (ColType::Float32, ColType::Float) => {
+            true
+        }
+        (ColType::BoundedString { .. }, ColType::String) => true,
+        (ColType::BoundedString { max_len: m }, ColType::BoundedString { max_len: n }) => m <= n,
+        (ColType::Tuple(a), ColType::Tuple(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.is_assignable_to(b))
+        }
+        (ColType::Tuple(a), to) if list_bounds(to).is_some() => {
+            let (b, min_len, max_len) = list_bounds(to).unwrap();
+            len_within(a.len(), min_len, max_len) && a.iter().all(|a| a.is_assignable_to(b))
+        }
+        (from, ColType::Tuple(b)) if list_bounds(from).is_some() => {
+            let (a, min_len, max_len) = list_bounds(from).unwrap();
+            min_len == b.len()
+                && max_len == Some(b.len())
+                && b.iter().all(|b| a.is_assignable_to(b))
+        }
+        (from, to) if list_bounds(from).is_some() && list_bounds(to).is_some() => {
+            let (a, from_min, from_max) = list_bounds(from).unwrap();
+            let (b, to_min, to_max) = list_bounds(to).unwrap();
+            to_min <= from_min
+                && match (from_max, to_max) {
+                    (_, None) => true,
+                    (Some(f), Some(t)) => f <= t,
+                    (None, Some(_)) => false,
+                }
+                && a.is_assignable_to(b)
+        }
+        (from, to) => from == to,
+    }
+}
+
+/// How much coercing `from` into `to` took, for [CoerceOptions::prefer_narrowest_union]:
+/// 0 if the value is unchanged, 1 if it stayed the same kind of value, as when an Int is
+/// widened to a Float, and 2 if it became another kind, as when a string is parsed as a Uuid
+fn coercion_cost(from: &DataValue, to: &DataValue) -> u8 {
+    if from == to {
+        0
+    } else if mem::discriminant(from) == mem::discriminant(to) {
+        1
+    } else {
+        2
+    }
+}
+
+fn len_within(len: usize, min_len: usize, max_len: Option<usize>) -> bool {
+    min_len <= len
+        && match max_len {
+            None => true,
+            Some(m) => len <= m,
+        }
+}
+
+/// Element type and length bounds of List types
+fn list_bounds(coltype: &ColType) -> Option<(&NullableColType, usize, Option<usize>)> {
+    match coltype {
+        ColType::List { eltype, len } => Some((eltype, len.unwrap_or(0), *len)),
+        ColType::BoundedList {
+            eltype,
+            min_len,
+            max_len,
+        } => Some((eltype, *min_len, *max_len)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("at {path}: {inner}")]
+#[diagnostic(code(eval::coercion_failed_at))]
+struct NestedCoercionFailure {
+    path: String,
+    inner: Report,
+}
+
+/// Prefixes the path of a coercion error with the index `i` of the element that failed,
+/// so that the error for a nested element reads like `at [1][2]: ...`
+fn nested_at(err: Report, i: usize) -> Report {
+    match err.downcast::<NestedCoercionFailure>() {
+        Ok(mut nested) => {
+            nested.path = format!("[{i}]{}", nested.path);
+            nested.into()
+        }
+        Err(err) => NestedCoercionFailure {
+            path: format!("[{i}]"),
+            inner: err,
+        }
+        .into(),
+    }
+}
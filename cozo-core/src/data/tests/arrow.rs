@@ -0,0 +1,38 @@
+/*
+ *  Copyright 2023, The Cozo Project Authors.
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ *  If a copy of the MPL was not distributed with this file,
+ *  You can obtain one at https://mozilla.org/MPL/2.0/.
+ *
+ */
+
+use arrow_array::{Array, Int64Array, StringArray};
+
+use crate::data::value::DataValue;
+use crate::parse::parse_type;
+
+#[test]
+fn int_array() {
+    let typ = parse_type("Int").unwrap();
+    let values = [DataValue::from(1), DataValue::from(2), DataValue::from(3)];
+    let arr = DataValue::to_arrow_array(&values, &typ).unwrap();
+    let arr = arr.as_any().downcast_ref::<Int64Array>().unwrap();
+    assert_eq!(arr.values(), &[1, 2, 3]);
+    assert_eq!(arr.null_count(), 0);
+
+    assert!(DataValue::to_arrow_array(&[DataValue::Null], &typ).is_err());
+    assert!(DataValue::to_arrow_array(&[DataValue::from("a")], &typ).is_err());
+}
+
+#[test]
+fn nullable_string_array() {
+    let typ = parse_type("String?").unwrap();
+    let values = [DataValue::from("a"), DataValue::Null, DataValue::from("c")];
+    let arr = DataValue::to_arrow_array(&values, &typ).unwrap();
+    let arr = arr.as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!(arr.len(), 3);
+    assert_eq!(arr.value(0), "a");
+    assert!(arr.is_null(1));
+    assert_eq!(arr.value(2), "c");
+}
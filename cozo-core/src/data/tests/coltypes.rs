@@ -0,0 +1,918 @@
+/*
+ *  Copyright 2022, The Cozo Project Authors.
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ *  If a copy of the MPL was not distributed with this file,
+ *  You can obtain one at https://mozilla.org/MPL/2.0/.
+ *
+ */
+
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::data::json::JsonValue;
+use crate::data::relation::{CoerceOptions, ColType, NullableColType, TypingCache};
+use crate::data::value::{DataValue, JsonData, Num, UuidWrapper, ValidityTs};
+use crate::parse::parse_type;
+
+const VLD: ValidityTs = ValidityTs(Reverse(0));
+
+#[test]
+fn tuple_length() {
+    let typ = parse_type("(Int, String)").unwrap();
+    let exact = DataValue::List(vec![DataValue::from(1), DataValue::from("a")]);
+    let long = DataValue::List(vec![
+        DataValue::from(1),
+        DataValue::from("a"),
+        DataValue::from(true),
+    ]);
+    let short = DataValue::List(vec![DataValue::from(1)]);
+
+    assert_eq!(typ.coerce(exact.clone(), VLD).unwrap(), exact);
+    assert!(typ.coerce(long.clone(), VLD).is_err());
+    assert!(typ.coerce(short.clone(), VLD).is_err());
+
+    let opts = CoerceOptions {
+        allow_extra_tuple_elems: true,
+        ..Default::default()
+    };
+    assert_eq!(typ.coerce_with(long, VLD, &opts).unwrap(), exact);
+    assert!(typ.coerce_with(short, VLD, &opts).is_err());
+}
+
+#[test]
+fn cast_json() {
+    let typ = parse_type("(Float, String, [Int])").unwrap();
+    let out = typ.cast_json(&json!([1, "a", [1.0, 2]])).unwrap();
+    assert_eq!(out, json!([1.0, "a", [1, 2]]));
+
+    let err = typ.cast_json(&json!([1, "a", [1, "x"]])).unwrap_err();
+    assert!(err.to_string().contains("[2][1]"));
+    assert!(typ.cast_json(&json!([1, "a"])).is_err());
+}
+
+#[test]
+fn lenient_bool() {
+    let typ = parse_type("Bool").unwrap();
+    assert!(typ.coerce(DataValue::from("yes"), VLD).is_err());
+
+    let opts = CoerceOptions {
+        lenient_bool: true,
+        ..Default::default()
+    };
+    for (s, expected) in [
+        ("yes", true),
+        ("True", true),
+        ("no", false),
+        ("FALSE", false),
+    ] {
+        assert_eq!(
+            typ.coerce_with(DataValue::from(s), VLD, &opts).unwrap(),
+            DataValue::from(expected)
+        );
+    }
+    assert!(typ
+        .coerce_with(DataValue::from("maybe"), VLD, &opts)
+        .is_err());
+
+    let opts = CoerceOptions {
+        lenient_bool: true,
+        truthy_strings: vec!["on".into()],
+        falsy_strings: vec!["off".into()],
+        ..Default::default()
+    };
+    assert_eq!(
+        typ.coerce_with(DataValue::from("on"), VLD, &opts).unwrap(),
+        DataValue::from(true)
+    );
+    assert!(typ.coerce_with(DataValue::from("yes"), VLD, &opts).is_err());
+}
+
+#[test]
+fn nullability_conversion() {
+    let int = parse_type("Int").unwrap();
+    let nullable_int = parse_type("Int?").unwrap();
+    assert_eq!(int.clone().into_nullable(), nullable_int);
+    assert_eq!(nullable_int.clone().into_nullable(), nullable_int);
+    assert_eq!(nullable_int.clone().into_required(), int);
+    assert_eq!(int.clone().into_required(), int);
+    assert_eq!(nullable_int.into_nullable().to_string(), "Int?");
+}
+
+#[test]
+fn nullable_list_does_not_leak_into_elements() {
+    let typ = parse_type("[Int]?").unwrap();
+    assert_eq!(typ.coerce(DataValue::Null, VLD).unwrap(), DataValue::Null);
+    let ints = DataValue::List(vec![DataValue::from(1), DataValue::from(2)]);
+    assert_eq!(typ.coerce(ints.clone(), VLD).unwrap(), ints);
+    let with_null = DataValue::List(vec![DataValue::from(1), DataValue::Null]);
+    assert!(typ.coerce(with_null.clone(), VLD).is_err());
+
+    let typ = parse_type("[Int?]").unwrap();
+    assert!(typ.coerce(DataValue::Null, VLD).is_err());
+    assert_eq!(typ.coerce(with_null.clone(), VLD).unwrap(), with_null);
+}
+
+#[test]
+fn typing_cache() {
+    let mut cache = TypingCache::new();
+    assert_eq!(
+        cache.coerce("Float", DataValue::from(1)).unwrap(),
+        DataValue::from(1.0)
+    );
+    let first = cache.get_or_parse("Float").unwrap() as *const _;
+    assert_eq!(
+        cache.coerce("Float", DataValue::from(2)).unwrap(),
+        DataValue::from(2.0)
+    );
+    let second = cache.get_or_parse("Float").unwrap() as *const _;
+    assert_eq!(first, second);
+    assert_eq!(cache.len(), 1);
+
+    assert!(cache.coerce("Flaot", DataValue::from(1)).is_err());
+    assert!(cache.coerce("Float", DataValue::from("x")).is_err());
+    assert_eq!(cache.len(), 1);
+}
+
+#[test]
+fn float32() {
+    let typ = parse_type("Float32").unwrap();
+    assert_eq!(typ.to_string(), "Float32");
+    assert_eq!(parse_type("Float64").unwrap(), parse_type("Float").unwrap());
+
+    assert_eq!(
+        typ.coerce(DataValue::from(0.5), VLD).unwrap(),
+        DataValue::from(0.5)
+    );
+    assert_eq!(
+        typ.coerce(DataValue::from(0.1), VLD).unwrap(),
+        DataValue::from(0.1f32 as f64)
+    );
+    assert_eq!(
+        typ.coerce(DataValue::from(3), VLD).unwrap(),
+        DataValue::from(3.0)
+    );
+    assert!(typ.coerce(DataValue::from(1e39), VLD).is_err());
+    assert!(typ.coerce(DataValue::from(-1e39), VLD).is_err());
+    assert_eq!(
+        typ.coerce(DataValue::from(f64::INFINITY), VLD).unwrap(),
+        DataValue::from(f64::INFINITY)
+    );
+}
+
+#[test]
+fn coerce_row() {
+    let types = vec![
+        parse_type("Int").unwrap(),
+        parse_type("Float").unwrap(),
+        parse_type("String?").unwrap(),
+    ];
+    let row = vec![DataValue::from(1), DataValue::from(2), DataValue::Null];
+    assert_eq!(
+        NullableColType::coerce_row(&types, row).unwrap(),
+        vec![DataValue::from(1), DataValue::from(2.0), DataValue::Null]
+    );
+
+    let short = vec![DataValue::from(1), DataValue::from(2.0)];
+    assert!(NullableColType::coerce_row(&types, short).is_err());
+
+    let bad = vec![DataValue::from(1), DataValue::from("x"), DataValue::Null];
+    let err = NullableColType::coerce_row(&types, bad).unwrap_err();
+    assert_eq!(err.to_string(), "at column 1");
+}
+
+#[test]
+fn bounded_string() {
+    let typ = parse_type("String(3)").unwrap();
+    assert_eq!(typ.to_string(), "String(3)");
+    assert_eq!(parse_type(&typ.to_string()).unwrap(), typ);
+
+    assert_eq!(
+        typ.coerce(DataValue::from("abc"), VLD).unwrap(),
+        DataValue::from("abc")
+    );
+    // lengths are counted in chars, not bytes
+    assert_eq!(
+        typ.coerce(DataValue::from("äöü"), VLD).unwrap(),
+        DataValue::from("äöü")
+    );
+    assert!(typ.coerce(DataValue::from("abcd"), VLD).is_err());
+    assert!(typ.coerce(DataValue::from(1), VLD).is_err());
+}
+
+#[test]
+fn coerce_json_inferred() {
+    let any = parse_type("Any").unwrap();
+    let inferred = |j| any.coerce_json_inferred(&j).unwrap().1.to_string();
+    assert_eq!(inferred(json!(1)), "Int");
+    assert_eq!(inferred(json!(1.5)), "Float");
+    assert_eq!(inferred(json!("a")), "String");
+    assert!(any.coerce_json_inferred(&json!(null)).is_err());
+    let nullable_any = parse_type("Any?").unwrap();
+    assert_eq!(
+        nullable_any.coerce_json_inferred(&json!(null)).unwrap().1,
+        nullable_any
+    );
+    assert_eq!(inferred(json!({"a": 1})), "Json");
+    assert_eq!(inferred(json!([1, 2])), "[Int]");
+    assert_eq!(inferred(json!([1, null])), "[Int?]");
+    assert_eq!(inferred(json!([1, "a"])), "(Int,String)");
+
+    let float = parse_type("Float").unwrap();
+    assert_eq!(
+        float.coerce_json_inferred(&json!(1)).unwrap(),
+        (DataValue::from(1.0), float.clone())
+    );
+    assert!(float.coerce_json_inferred(&json!("a")).is_err());
+}
+
+#[test]
+fn to_sql_type() {
+    let sql = |s: &str| parse_type(s).unwrap().to_sql_type();
+    assert_eq!(sql("Int"), "BIGINT NOT NULL");
+    assert_eq!(sql("Int?"), "BIGINT");
+    assert_eq!(sql("Float?"), "DOUBLE PRECISION");
+    assert_eq!(sql("Float32?"), "REAL");
+    assert_eq!(sql("String?"), "TEXT");
+    assert_eq!(sql("String(20)?"), "VARCHAR(20)");
+    assert_eq!(sql("Bool?"), "BOOLEAN");
+    assert_eq!(sql("Uuid?"), "UUID");
+    assert_eq!(sql("Bytes?"), "BYTEA");
+    assert_eq!(sql("Json?"), "JSONB");
+    assert_eq!(sql("[Int]"), "JSONB NOT NULL");
+    assert_eq!(sql("(Int, String)?"), "JSONB");
+}
+
+#[test]
+fn json_null_against_any() {
+    let any = parse_type("Any?").unwrap();
+    assert_eq!(
+        DataValue::from_json_with_schema(&json!(null), &any).unwrap(),
+        DataValue::Null
+    );
+    assert!(DataValue::from_json_with_schema(&json!(null), &parse_type("Any").unwrap()).is_err());
+    // Bot is internal only and never produced from JSON
+    assert!(matches!(
+        DataValue::from_json_with_schema(&json!({"$bottom": true}), &any).unwrap(),
+        DataValue::Json(_)
+    ));
+    assert!(any.coerce(DataValue::Bot, VLD).is_err());
+}
+
+#[test]
+fn quantity() {
+    let typ = parse_type(r#"Float<"celsius">"#).unwrap();
+    assert_eq!(typ.to_string(), r#"Float<"celsius">"#);
+    assert_eq!(typ.unit(), Some("celsius"));
+    assert_eq!(parse_type("Float").unwrap().unit(), None);
+    assert_eq!(
+        typ.coerce(DataValue::from(21), VLD).unwrap(),
+        DataValue::from(21.0)
+    );
+    assert!(typ.coerce(DataValue::from("warm"), VLD).is_err());
+    assert!(typ.coerce(DataValue::Null, VLD).is_err());
+
+    let pct = parse_type(r#"?Int<"%">"#).unwrap();
+    assert_eq!(pct.unit(), Some("%"));
+    assert_eq!(pct.coerce(DataValue::Null, VLD).unwrap(), DataValue::Null);
+    assert_eq!(pct.to_sql_type(), "BIGINT");
+
+    let readings = parse_type(r#"[Float32<"kPa">]"#).unwrap();
+    assert_eq!(
+        readings.cast_json(&json!([1, 2.5])).unwrap(),
+        json!([1.0, 2.5])
+    );
+    assert!(parse_type(r#"String<"m">"#).is_err());
+
+    for unit in ["µm", r#"5" \ 'x'"#, "per\n\u{1}line"] {
+        let typ = NullableColType {
+            coltype: ColType::Quantity {
+                base: Box::new(ColType::Int),
+                unit: unit.to_string(),
+            },
+            nullable: false,
+        };
+        assert_eq!(parse_type(&typ.to_string()).unwrap(), typ, "{typ}");
+    }
+    assert_eq!(
+        parse_type(r#"Int<"say \"hi\"">"#).unwrap().to_string(),
+        r#"Int<"say \"hi\"">"#
+    );
+}
+
+#[test]
+fn bytes() {
+    let typ = parse_type("Bytes").unwrap();
+    assert_eq!(typ.to_string(), "Bytes");
+    let b = DataValue::Bytes(vec![0, 1, 255]);
+    assert_eq!(typ.coerce(b.clone(), VLD).unwrap(), b);
+    assert_eq!(typ.coerce(DataValue::from("AAH/"), VLD).unwrap(), b);
+    let err = typ.coerce(DataValue::from(1), VLD).unwrap_err();
+    assert!(err.to_string().contains("expected type Bytes"));
+    assert!(typ.coerce(DataValue::List(vec![]), VLD).is_err());
+}
+
+#[test]
+fn infer_report() {
+    let row = |a: DataValue, b: DataValue| DataValue::List(vec![a, b]);
+
+    let (typ, notes) = NullableColType::infer_report(&[
+        row(DataValue::from(1), DataValue::from("a")),
+        row(DataValue::from(2), DataValue::Null),
+    ]);
+    assert_eq!(typ.to_string(), "(Int,String?)");
+    assert!(notes.is_empty());
+
+    let (typ, notes) = NullableColType::infer_report(&[
+        row(DataValue::from(1), DataValue::from("a")),
+        row(DataValue::from(2), DataValue::from(3)),
+        row(DataValue::from(2.5), DataValue::Null),
+        row(DataValue::from(4), DataValue::from(true)),
+    ]);
+    assert_eq!(typ.to_string(), "(Float,Any?)");
+    assert_eq!(
+        notes,
+        vec![
+            "value at [1] saw both String and Int, widened to Any",
+            "value at [0] saw both Int and Float, widened to Float",
+        ]
+    );
+
+    let (typ, notes) = NullableColType::infer_report(&[DataValue::from(1), DataValue::from("a")]);
+    assert_eq!(typ.to_string(), "Any");
+    assert_eq!(notes, vec!["value saw both Int and String, widened to Any"]);
+
+    let tags = |n: i64| DataValue::List((0..n).map(DataValue::from).collect());
+    let (typ, notes) = NullableColType::infer_report(&[tags(1), tags(3), tags(0)]);
+    assert_eq!(typ.to_string(), "[Int]");
+    assert!(notes.is_empty());
+
+    assert_eq!(
+        NullableColType::infer_report(&[]).0,
+        parse_type("Any?").unwrap()
+    );
+}
+
+#[test]
+fn int_to_float() {
+    let typ = parse_type("Float").unwrap();
+    assert_eq!(
+        typ.coerce(DataValue::from(3), VLD).unwrap(),
+        DataValue::from(3.0)
+    );
+    assert!(matches!(
+        typ.coerce(DataValue::from(3), VLD).unwrap(),
+        DataValue::Num(Num::Float(_))
+    ));
+    assert_eq!(
+        typ.coerce(DataValue::from(2.5), VLD).unwrap(),
+        DataValue::from(2.5)
+    );
+    assert!(typ.coerce(DataValue::from("3"), VLD).is_err());
+
+    let exact = 1i64 << 53;
+    assert_eq!(
+        typ.coerce(DataValue::from(exact), VLD).unwrap(),
+        DataValue::from(exact as f64)
+    );
+    let err = typ.coerce(DataValue::from(exact + 1), VLD).unwrap_err();
+    assert!(err.to_string().contains(&(exact + 1).to_string()));
+    assert!(typ.coerce(DataValue::from(i64::MAX), VLD).is_err());
+    assert!(typ.coerce(DataValue::from(i64::MIN), VLD).is_ok());
+
+    let list = parse_type("[Float]").unwrap();
+    assert_eq!(
+        list.coerce(
+            DataValue::List(vec![DataValue::from(1), DataValue::from(0.5)]),
+            VLD
+        )
+        .unwrap(),
+        DataValue::List(vec![DataValue::from(1.0), DataValue::from(0.5)])
+    );
+}
+
+#[test]
+fn uuid_from_string() {
+    let typ = parse_type("Uuid").unwrap();
+    let id = Uuid::new_v4();
+    let native = DataValue::Uuid(UuidWrapper(id));
+    assert_eq!(typ.coerce(native.clone(), VLD).unwrap(), native);
+    assert_eq!(
+        typ.coerce(DataValue::from(id.hyphenated().to_string()), VLD)
+            .unwrap(),
+        native
+    );
+    let err = typ.coerce(DataValue::from("not-a-uuid"), VLD).unwrap_err();
+    assert!(err.to_string().contains("expected type Uuid"));
+    assert!(typ.coerce(DataValue::from(1), VLD).is_err());
+}
+
+#[test]
+fn int_out_of_range() {
+    let typ = parse_type("Int").unwrap();
+    let code = |j: JsonValue| typ.cast_json(&j).unwrap_err().code().map(|c| c.to_string());
+    let out_of_range = Some("eval::coercion_int_out_of_range".to_string());
+    assert_eq!(code(json!(u64::MAX)), out_of_range);
+    assert_eq!(code(json!(1e20)), out_of_range);
+    assert_eq!(code(json!(-1e20)), out_of_range);
+    assert_eq!(code(json!("1")), Some("eval::coercion_failed".to_string()));
+    assert_eq!(code(json!(1.5)), Some("eval::coercion_failed".to_string()));
+    assert_eq!(typ.cast_json(&json!(i64::MIN)).unwrap(), json!(i64::MIN));
+    assert_eq!(typ.cast_json(&json!(i64::MAX)).unwrap(), json!(i64::MAX));
+
+    let json_num = |j: JsonValue| DataValue::Json(JsonData(j));
+    assert_eq!(
+        typ.coerce(json_num(json!(5)), VLD).unwrap(),
+        DataValue::from(5)
+    );
+    assert_eq!(
+        typ.coerce(json_num(json!(-5)), VLD).unwrap(),
+        DataValue::from(-5)
+    );
+    let err = typ.coerce(json_num(json!(u64::MAX)), VLD).unwrap_err();
+    assert_eq!(err.code().map(|c| c.to_string()), out_of_range);
+    let err = typ.coerce(json_num(json!(1.5)), VLD).unwrap_err();
+    assert_eq!(
+        err.code().map(|c| c.to_string()),
+        Some("eval::coercion_failed".to_string())
+    );
+}
+
+#[test]
+fn is_assignable_to() {
+    let cases = [
+        ("Int", "Any", true),
+        ("[String]?", "Any?", true),
+        ("Int?", "Any", false),
+        ("Int", "Float", true),
+        ("Float", "Int", false),
+        ("Int", "Int?", true),
+        ("Int?", "Int", false),
+        ("String", "Int", false),
+        ("String(5)", "String", true),
+        ("String", "String(5)", false),
+        ("String(5)", "String(10)", true),
+        ("[Int]", "[Float]", true),
+        ("[Int; 3]", "[Float]", true),
+        ("[Int]", "[Float; 3]", false),
+        ("[Float]", "[Int]", false),
+        ("[Int]", "[Int?]", true),
+        ("[Int?]", "[Int]", false),
+        ("(Int, String)", "(Float, String?)", true),
+        ("(Int, String)", "(Int, Int)", false),
+        ("(Int, String)", "(Int, String, Bool)", false),
+        ("(Int, Int)", "[Float; 2]", true),
+        ("(Int, Int)", "[Int; 3]", false),
+        ("[Int; 2]", "(Int, Float)", true),
+        ("[Int]", "(Int, Int)", false),
+        (r#"Int<"m">"#, "Float", true),
+        ("Int", r#"Float<"m">"#, true),
+        ("Uuid", "String", false),
+        ("Any", "Int", false),
+    ];
+    for (from, to, expected) in cases {
+        let from_t = parse_type(from).unwrap();
+        let to_t = parse_type(to).unwrap();
+        assert_eq!(
+            from_t.is_assignable_to(&to_t),
+            expected,
+            "{from} assignable to {to}"
+        );
+    }
+}
+
+#[test]
+fn default_value() {
+    let default = |s: &str| parse_type(s).unwrap().default_value();
+    assert_eq!(default("Bool"), Some(DataValue::from(false)));
+    assert_eq!(default("Int"), Some(DataValue::from(0)));
+    assert!(matches!(
+        default("Float"),
+        Some(DataValue::Num(Num::Float(f))) if f == 0.0
+    ));
+    assert_eq!(default("String(3)"), Some(DataValue::from("")));
+    assert_eq!(default("Bytes"), Some(DataValue::Bytes(vec![])));
+    assert_eq!(default("Uuid?"), Some(DataValue::Null));
+    assert_eq!(default("[Int]"), Some(DataValue::List(vec![])));
+    assert_eq!(
+        default("[Bool; 2]"),
+        Some(DataValue::List(vec![DataValue::from(false); 2]))
+    );
+    assert_eq!(
+        default(r#"(Int, (String, Float<"m">?), [Int])"#),
+        Some(DataValue::List(vec![
+            DataValue::from(0),
+            DataValue::List(vec![DataValue::from(""), DataValue::Null]),
+            DataValue::List(vec![]),
+        ]))
+    );
+    assert_eq!(default("<F32; 2>").unwrap().to_string(), "vec([0.0, 0.0])");
+    assert_eq!(default("Any"), None);
+    assert_eq!(default("Uuid"), None);
+    assert_eq!(default("Validity"), None);
+    assert_eq!(default("(Int, Uuid)"), None);
+}
+
+#[test]
+fn decode_base64() {
+    let typ = parse_type("Bytes").unwrap();
+    let decoded = typ.coerce(DataValue::from("aGVsbG8="), VLD).unwrap();
+    assert_eq!(decoded, DataValue::Bytes(b"hello".to_vec()));
+    let err = typ.coerce(DataValue::from("not base64!"), VLD).unwrap_err();
+    assert!(err.to_string().contains("base64"));
+    // any string that is valid base64 is taken by default, even if not meant as such
+    assert!(CoerceOptions::default().decode_base64);
+    assert_eq!(
+        typ.coerce(DataValue::from("abcd"), VLD).unwrap(),
+        DataValue::Bytes(vec![0x69, 0xb7, 0x1d])
+    );
+
+    let opts = CoerceOptions {
+        decode_base64: false,
+        ..Default::default()
+    };
+    assert!(typ
+        .coerce_with(DataValue::from("aGVsbG8="), VLD, &opts)
+        .is_err());
+    assert_eq!(
+        typ.coerce_with(decoded.clone(), VLD, &opts).unwrap(),
+        decoded
+    );
+}
+
+#[test]
+fn coerce_collecting() {
+    let typ = parse_type("[(Int, String)]").unwrap();
+    let row = |a: DataValue, b: DataValue| DataValue::List(vec![a, b]);
+    let good = DataValue::List(vec![
+        row(DataValue::from(1.0), DataValue::from("a")),
+        row(DataValue::from(2), DataValue::from("b")),
+    ]);
+    assert_eq!(
+        typ.coerce_collecting(good.clone()).unwrap(),
+        typ.coerce(good, VLD).unwrap()
+    );
+
+    let bad = DataValue::List(vec![
+        row(DataValue::from("x"), DataValue::from("a")),
+        row(DataValue::from(2), DataValue::from("b")),
+        row(DataValue::from(3), DataValue::from(4)),
+        DataValue::from(5),
+    ]);
+    let errors = typ.coerce_collecting(bad.clone()).unwrap_err();
+    let messages = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            r#"at [0][0]: data coercion failed: expected type Int, got value "x""#,
+            "at [2][1]: data coercion failed: expected type String, got value 4",
+            "at [3]: data coercion failed: expected type (Int,String), got value 5"
+        ]
+    );
+    assert!(typ.coerce(bad, VLD).is_err());
+
+    let errors = parse_type("Int")
+        .unwrap()
+        .coerce_collecting(DataValue::from("x"))
+        .unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].to_string().contains("expected type Int"));
+}
+
+#[test]
+fn union() {
+    let typ = parse_type("Int | String").unwrap();
+    assert_eq!(typ.to_string(), "Int | String");
+    assert_eq!(parse_type(&typ.to_string()).unwrap(), typ);
+    assert_eq!(
+        typ.coerce(DataValue::from(1.0), VLD).unwrap(),
+        DataValue::from(1)
+    );
+    assert_eq!(
+        typ.coerce(DataValue::from("a"), VLD).unwrap(),
+        DataValue::from("a")
+    );
+    let err = typ.coerce(DataValue::from(true), VLD).unwrap_err();
+    assert!(err.to_string().contains("no member of type Int | String"));
+    let member_errs: Vec<_> = err.related().unwrap().map(|e| e.to_string()).collect();
+    assert_eq!(
+        member_errs,
+        vec![
+            "not accepted by member Int",
+            "not accepted by member String"
+        ]
+    );
+    assert!(typ.coerce(DataValue::Null, VLD).is_err());
+
+    let nullable = parse_type("[Int] | ?Bool").unwrap();
+    assert!(nullable.nullable);
+    assert_eq!(nullable.to_string(), "[Int] | Bool?");
+    assert_eq!(parse_type(&nullable.to_string()).unwrap(), nullable);
+    assert_eq!(
+        nullable.coerce(DataValue::Null, VLD).unwrap(),
+        DataValue::Null
+    );
+
+    let list = parse_type("[Int | Float]").unwrap();
+    assert_eq!(list.cast_json(&json!([1, 1.5])).unwrap(), json!([1, 1.5]));
+
+    assert_eq!(parse_type("Int | Int").unwrap(), parse_type("Int").unwrap());
+    assert_eq!(
+        parse_type("Int | Any | String").unwrap(),
+        parse_type("Any").unwrap()
+    );
+    let nested = NullableColType::union([typ.clone(), parse_type("String | Bool").unwrap()]);
+    assert_eq!(nested, parse_type("Int | String | Bool").unwrap());
+    assert!(parse_type("Int").unwrap().is_assignable_to(&typ));
+    assert!(!parse_type("Bool").unwrap().is_assignable_to(&typ));
+    assert!(typ.is_assignable_to(&parse_type("Int | String | Bool").unwrap()));
+}
+
+#[test]
+fn simplify() {
+    let req = |coltype| NullableColType {
+        coltype,
+        nullable: false,
+    };
+    let any_union = ColType::Union(vec![ColType::Int, ColType::Any]);
+    assert_eq!(
+        req(any_union.clone()).simplify(),
+        parse_type("Any").unwrap()
+    );
+    assert_eq!(
+        req(ColType::List {
+            eltype: Box::new(req(any_union)),
+            len: None
+        })
+        .simplify(),
+        parse_type("[Any]").unwrap()
+    );
+    assert_eq!(parse_type("[Any]").unwrap().simplify().to_string(), "[Any]");
+
+    // ??Int: nullability only nests as a nullable union of a single member
+    let doubly_nullable = NullableColType {
+        coltype: ColType::Union(vec![ColType::Int]),
+        nullable: true,
+    };
+    assert_eq!(doubly_nullable.simplify(), parse_type("?Int").unwrap());
+
+    let nested = NullableColType {
+        coltype: ColType::Union(vec![
+            ColType::Int,
+            ColType::Union(vec![ColType::String, ColType::Int]),
+        ]),
+        nullable: true,
+    };
+    assert_eq!(nested.simplify().to_string(), "Int | String?");
+    let typ = parse_type("(Int, [String | Bool]?)").unwrap();
+    assert_eq!(typ.clone().simplify(), typ);
+}
+
+#[test]
+fn nested_error_path() {
+    let message = |typ: &str, v: JsonValue| {
+        parse_type(typ)
+            .unwrap()
+            .coerce(DataValue::from(v), VLD)
+            .unwrap_err()
+            .to_string()
+    };
+    let msg = message("[(Int, [Int])]", json!([[1, [1, 2]], [2, [3, "x"]]]));
+    assert!(msg.starts_with("at [1][1][1]: "), "{msg}");
+    assert!(msg.contains("expected type Int"), "{msg}");
+
+    let msg = message("(String, [Float; 2])", json!(["a", [1.0]]));
+    assert!(
+        msg.starts_with("at [1]: bad list length: expected datatype [Float;2]"),
+        "{msg}"
+    );
+
+    let msg = message("[[Int]]", json!([[], null]));
+    assert_eq!(
+        msg,
+        "at [1]: encountered null value for non-null type [Int]"
+    );
+    assert!(!message("Int", json!("x")).starts_with("at "));
+
+    let cast_message = |typ: &str, v: JsonValue| {
+        parse_type(typ)
+            .unwrap()
+            .cast_json(&v)
+            .unwrap_err()
+            .to_string()
+    };
+    for typ in [
+        "[[Int]]",
+        "[[Int; 2]; 2]",
+        "[[Int; 1..3]]",
+        "([Int], (Int, Int))",
+    ] {
+        let msg = cast_message(typ, json!([[1, 2], [3, "x"]]));
+        assert!(
+            msg.starts_with(r#"at [1][1]: data coercion failed: expected type Int, got value "x""#),
+            "{typ}: {msg}"
+        );
+    }
+}
+
+#[test]
+fn list_length_bounds() {
+    let ints = |n: i64| DataValue::List((0..n).map(DataValue::from).collect());
+
+    let exact = parse_type("[Float; 3]").unwrap();
+    assert!(exact.coerce(ints(3), VLD).is_ok());
+    assert!(exact.coerce(ints(2), VLD).is_err());
+    assert!(exact.coerce(ints(4), VLD).is_err());
+
+    let open = parse_type("[Int]").unwrap();
+    assert!(open.coerce(ints(0), VLD).is_ok());
+    assert!(open.coerce(ints(100), VLD).is_ok());
+
+    let bounded = parse_type("[Int; 2..4]").unwrap();
+    assert_eq!(bounded.to_string(), "[Int;2..4]");
+    assert_eq!(parse_type(&bounded.to_string()).unwrap(), bounded);
+    assert!(bounded.coerce(ints(1), VLD).is_err());
+    assert_eq!(bounded.coerce(ints(2), VLD).unwrap(), ints(2));
+    assert_eq!(bounded.coerce(ints(4), VLD).unwrap(), ints(4));
+    let err = bounded.coerce(ints(5), VLD).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "bad list length: expected datatype [Int;2..4], got length 5"
+    );
+
+    let at_most = parse_type("[Int; ..10]").unwrap();
+    assert_eq!(at_most.to_string(), "[Int;..10]");
+    assert!(at_most.coerce(ints(0), VLD).is_ok());
+    assert!(at_most.coerce(ints(11), VLD).is_err());
+    let at_least = parse_type("[String?; 1..]").unwrap();
+    assert_eq!(at_least.to_string(), "[String?;1..]");
+    assert!(at_least.coerce(ints(0), VLD).is_err());
+    assert_eq!(
+        at_least.default_value(),
+        Some(DataValue::List(vec![DataValue::Null]))
+    );
+    assert!(parse_type("[Int; 5..2]").is_err());
+
+    assert!(exact.is_assignable_to(&parse_type("[Float; 2..3]").unwrap()));
+    assert!(bounded.is_assignable_to(&parse_type("[Float; ..4]").unwrap()));
+    assert!(!at_least.is_assignable_to(&parse_type("[String?; ..4]").unwrap()));
+    assert!(parse_type("(Int, Int)").unwrap().is_assignable_to(&bounded));
+}
+
+#[test]
+fn hash_consistent_with_eq() {
+    let types = [
+        "Int",
+        "Int?",
+        "?Int",
+        "[Int; 2]",
+        "[Int;2]",
+        "[Int; 1 + 1]",
+        "(Int, String)",
+        "Float64",
+        "Float",
+        "Int | String",
+        "String | Int",
+        "[Int; 0..2]",
+    ]
+    .map(|s| parse_type(s).unwrap());
+    let set = types.iter().cloned().collect::<HashSet<_>>();
+    assert_eq!(set.len(), 8);
+    for t in &types {
+        assert!(set.contains(t));
+    }
+}
+
+#[test]
+fn unify() {
+    let unify = |a: &str, b: &str| {
+        let a = parse_type(a).unwrap();
+        let b = parse_type(b).unwrap();
+        let unified = a.unify(&b);
+        assert_eq!(b.unify(&a).nullable, unified.nullable);
+        assert!(a.is_assignable_to(&unified));
+        assert!(b.is_assignable_to(&unified));
+        unified.to_string()
+    };
+    assert_eq!(unify("Int", "Int"), "Int");
+    assert_eq!(unify("Int", "Float"), "Float");
+    assert_eq!(unify("Float32", "Int"), "Float32");
+    assert_eq!(unify("Int", "Int?"), "Int?");
+    assert_eq!(unify("Int?", "Float"), "Float?");
+    assert_eq!(unify("String(3)", "String"), "String");
+    assert_eq!(unify("[Int]", "[Float]"), "[Float]");
+    assert_eq!(unify("[Int]", "[String]"), "[Int | String]");
+    assert_eq!(unify("(Int, Bool)", "(Float, Bool?)"), "(Float,Bool?)");
+    assert_eq!(unify("Int", "Any"), "Any");
+    assert_eq!(unify("Int", "String"), "Int | String");
+    assert_eq!(unify("Int?", "String"), "Int | String?");
+    assert_eq!(unify("Int | String", "Bool"), "Int | String | Bool");
+    assert_eq!(unify("[Int; 2]", "(Int, String)"), "[Int;2] | (Int,String)");
+}
+
+#[test]
+fn inferred_typing() {
+    let typing = |v: DataValue| v.inferred_typing().to_string();
+    assert_eq!(typing(DataValue::Null), "Any?");
+    assert_eq!(typing(DataValue::from(true)), "Bool");
+    assert_eq!(typing(DataValue::from(1)), "Int");
+    assert_eq!(typing(DataValue::from(1.5)), "Float");
+    assert_eq!(typing(DataValue::from("a")), "String");
+    assert_eq!(typing(DataValue::Bytes(vec![1])), "Bytes");
+    assert_eq!(typing(DataValue::Uuid(UuidWrapper(Uuid::nil()))), "Uuid");
+    assert_eq!(typing(DataValue::from(&json!({"a": 1}))), "Json");
+    assert_eq!(typing(DataValue::List(vec![])), "[Any]");
+    assert_eq!(
+        typing(DataValue::List(vec![
+            DataValue::from(1),
+            DataValue::Null,
+            DataValue::from(2)
+        ])),
+        "[Int?]"
+    );
+    assert_eq!(
+        typing(DataValue::List(vec![
+            DataValue::from(1),
+            DataValue::from("a")
+        ])),
+        "(Int,String)"
+    );
+    assert_eq!(
+        typing(DataValue::Set(
+            [DataValue::from("a"), DataValue::from("b")].into()
+        )),
+        "[String]"
+    );
+}
+
+#[test]
+fn union_narrowest_member() {
+    let typ = parse_type("Float | Int").unwrap();
+    assert_eq!(
+        typ.coerce(DataValue::from(1), VLD).unwrap(),
+        DataValue::from(1.0)
+    );
+    let narrowest = CoerceOptions {
+        prefer_narrowest_union: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        typ.coerce_with(DataValue::from(1), VLD, &narrowest)
+            .unwrap(),
+        DataValue::from(1)
+    );
+    assert_eq!(
+        typ.coerce_with(DataValue::from(1.5), VLD, &narrowest)
+            .unwrap(),
+        DataValue::from(1.5)
+    );
+
+    let id = Uuid::new_v4();
+    let typ = parse_type("Uuid | String").unwrap();
+    let as_str = DataValue::from(id.to_string());
+    assert_eq!(
+        typ.coerce(as_str.clone(), VLD).unwrap(),
+        DataValue::uuid(id)
+    );
+    assert_eq!(
+        typ.coerce_with(as_str.clone(), VLD, &narrowest).unwrap(),
+        as_str
+    );
+}
+
+#[test]
+fn union_member_index() {
+    let opts = CoerceOptions::default();
+    let typ = parse_type("Uuid | Int?").unwrap();
+    assert_eq!(
+        typ.coerce_union(DataValue::from(3), VLD, &opts).unwrap(),
+        (Some(1), DataValue::from(3))
+    );
+    assert_eq!(
+        typ.coerce_union(DataValue::Null, VLD, &opts).unwrap(),
+        (None, DataValue::Null)
+    );
+    assert!(typ.coerce_union(DataValue::from("x"), VLD, &opts).is_err());
+
+    let typ = parse_type("Float | Int").unwrap();
+    let narrowest = CoerceOptions {
+        prefer_narrowest_union: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        typ.coerce_union(DataValue::from(1), VLD, &narrowest)
+            .unwrap(),
+        (Some(1), DataValue::from(1))
+    );
+
+    assert_eq!(
+        parse_type("Int")
+            .unwrap()
+            .coerce_union(DataValue::from(2), VLD, &opts)
+            .unwrap(),
+        (Some(0), DataValue::from(2))
+    );
+}
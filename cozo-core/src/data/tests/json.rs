@@ -7,10 +7,18 @@
  *
  */
 
+use std::cmp::Reverse;
+
+use ndarray::array;
 use serde_json::json;
+use uuid::Uuid;
 
-use crate::data::json::JsonValue;
-use crate::data::value::DataValue;
+use crate::data::json::{
+    datavalue_from_json_with_opts, datavalue_to_json_with_opts, JsonOptions, JsonValue,
+    NonFiniteFloatPolicy, TimestampFormat,
+};
+use crate::data::value::{DataValue, JsonData, UuidWrapper, Validity, ValidityTs, Vector};
+use crate::parse::parse_type;
 
 #[test]
 fn bad_values() {
@@ -19,3 +27,332 @@ fn bad_values() {
     println!("{}", JsonValue::from(DataValue::from(f64::NEG_INFINITY)));
     println!("{}", JsonValue::from(DataValue::from(f64::NAN)));
 }
+
+#[test]
+fn tagged_large_ints() {
+    let opts = JsonOptions {
+        tag_large_ints: true,
+        ..Default::default()
+    };
+    let small = DataValue::from(1i64 << 40);
+    let large = DataValue::from(-(1i64 << 60));
+
+    let small_json = datavalue_to_json_with_opts(small.clone(), &opts).unwrap();
+    assert_eq!(small_json, json!(1i64 << 40));
+    let large_json = datavalue_to_json_with_opts(large.clone(), &opts).unwrap();
+    assert_eq!(large_json, json!({"$int": (-(1i64 << 60)).to_string()}));
+
+    let both = DataValue::List(vec![small, large]);
+    let both_json = datavalue_to_json_with_opts(both.clone(), &opts).unwrap();
+    assert_eq!(
+        datavalue_from_json_with_opts(&both_json, &opts).unwrap(),
+        both
+    );
+
+    assert!(datavalue_from_json_with_opts(&json!({"$int": "abc"}), &opts).is_err());
+    assert!(matches!(
+        datavalue_from_json_with_opts(&large_json, &JsonOptions::default()).unwrap(),
+        DataValue::Json(_)
+    ));
+}
+
+#[test]
+fn float_format() {
+    let third = DataValue::from(1.0 / 3.0);
+    assert_eq!(third.to_json_with_float_format(3).to_string(), "0.333");
+    assert_eq!(third.to_json_with_float_format(0).to_string(), "0.0");
+    assert_eq!(
+        DataValue::from(2.0 / 3.0)
+            .to_json_with_float_format(2)
+            .to_string(),
+        "0.67"
+    );
+    let nested = DataValue::List(vec![third, DataValue::from(1), DataValue::from("x")]);
+    assert_eq!(nested.to_json_with_float_format(1), json!([0.3, 1, "x"]));
+    assert_eq!(
+        DataValue::from(f64::INFINITY).to_json_with_float_format(2),
+        json!("INFINITY")
+    );
+}
+
+#[test]
+fn objects_round_trip() {
+    let nested = json!({"a": {"b": [1, {"c": null}]}, "d": "e"});
+    let v = DataValue::from(&nested);
+    assert!(matches!(v, DataValue::Json(_)));
+    assert_eq!(JsonValue::from(v), nested);
+
+    let in_array = json!([{"x": 1}, 2]);
+    let v = DataValue::from(in_array.clone());
+    assert!(matches!(v.get_slice().unwrap()[0], DataValue::Json(_)));
+    assert_eq!(JsonValue::from(v), in_array);
+
+    // duplicate keys are resolved by the JSON parser, the last one wins
+    let dup: JsonValue = serde_json::from_str(r#"{"k": 1, "k": 2}"#).unwrap();
+    assert_eq!(JsonValue::from(DataValue::from(&dup)), json!({"k": 2}));
+}
+
+#[test]
+fn tagged_bytes() {
+    let opts = JsonOptions {
+        tag_bytes: true,
+        ..Default::default()
+    };
+    for bytes in [
+        vec![],
+        vec![0u8],
+        vec![0, 255, 128, 10, 13],
+        (0..=255).collect(),
+    ] {
+        let v = DataValue::Bytes(bytes.clone());
+        let j = datavalue_to_json_with_opts(v, &opts).unwrap();
+        assert!(j.get("$bytes").is_some());
+        let back = datavalue_from_json_with_opts(&j, &opts).unwrap();
+        assert_eq!(back.get_bytes(), Some(bytes.as_slice()));
+    }
+
+    assert!(datavalue_from_json_with_opts(&json!({"$bytes": "not base64!"}), &opts).is_err());
+    assert!(datavalue_from_json_with_opts(&json!({"$bytes": 1}), &opts).is_err());
+    assert_eq!(
+        JsonValue::from(DataValue::Bytes(vec![1, 2])),
+        datavalue_to_json_with_opts(DataValue::Bytes(vec![1, 2]), &JsonOptions::default()).unwrap()
+    );
+}
+
+#[test]
+fn strings_to_valid_json() {
+    // Str holds Rust strings, which are always valid UTF-8. Invalid input is
+    // replaced when the string is built, and everything else is escaped by serde_json.
+    let lossy = String::from_utf8_lossy(&[b'a', 0xff, 0xfe, b'b']).into_owned();
+    for s in [
+        lossy.as_str(),
+        "quote \" backslash \\ newline \n nul \0",
+        "\u{1F600} \u{FFFD} \u{10FFFF}",
+    ] {
+        let text = JsonValue::from(DataValue::from(s)).to_string();
+        let parsed: JsonValue = serde_json::from_str(&text).unwrap();
+        assert_eq!(parsed, json!(s));
+    }
+}
+
+#[test]
+fn uuid_from_json_with_schema() {
+    let typ = parse_type("Uuid").unwrap();
+    let id = Uuid::new_v4();
+    for s in [id.hyphenated().to_string(), id.urn().to_string()] {
+        assert_eq!(
+            DataValue::from_json_with_schema(&json!(s), &typ).unwrap(),
+            DataValue::Uuid(UuidWrapper(id))
+        );
+    }
+    let err = DataValue::from_json_with_schema(&json!("not-a-uuid"), &typ).unwrap_err();
+    assert!(err.to_string().contains("not-a-uuid"));
+
+    let list = parse_type("[Uuid]").unwrap();
+    let err = DataValue::from_json_with_schema(&json!([id.to_string(), "x"]), &list).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"at [1]: data coercion failed: expected type Uuid, got value "x""#
+    );
+}
+
+#[test]
+fn large_json_ints() {
+    assert_eq!(DataValue::from(json!(i64::MAX)), DataValue::from(i64::MAX));
+    assert_eq!(DataValue::from(&json!(i64::MIN)), DataValue::from(i64::MIN));
+
+    // beyond i64 ints become floats, so that they still compare and compute as numbers
+    assert_eq!(
+        DataValue::from(json!(u64::MAX)),
+        DataValue::from(u64::MAX as f64)
+    );
+    let v = DataValue::from(&json!(i64::MAX as u64 + 1));
+    assert_eq!(v, DataValue::from(2f64.powi(63)));
+    assert!(v > DataValue::from(i64::MAX));
+
+    let exact = JsonOptions {
+        exact_large_ints: true,
+        ..Default::default()
+    };
+    for n in [u64::MAX, i64::MAX as u64 + 1] {
+        let v = datavalue_from_json_with_opts(&json!(n), &exact).unwrap();
+        assert_eq!(v, DataValue::Json(JsonData(json!(n))));
+        assert_eq!(
+            datavalue_to_json_with_opts(v, &exact).unwrap().as_u64(),
+            Some(n)
+        );
+    }
+    assert_eq!(
+        datavalue_from_json_with_opts(&json!([i64::MAX, i64::MIN]), &exact).unwrap(),
+        DataValue::List(vec![DataValue::from(i64::MAX), DataValue::from(i64::MIN)])
+    );
+
+    assert_eq!(DataValue::from(json!(1.5)), DataValue::from(1.5));
+}
+
+#[test]
+fn non_finite_float_policies() {
+    let to_json = |f: f64, policy| {
+        let opts = JsonOptions {
+            non_finite_floats: policy,
+            ..Default::default()
+        };
+        datavalue_to_json_with_opts(DataValue::from(f), &opts)
+    };
+
+    use NonFiniteFloatPolicy::*;
+    assert_eq!(to_json(f64::NAN, Plain).unwrap(), json!(null));
+    assert_eq!(to_json(f64::INFINITY, Plain).unwrap(), json!("INFINITY"));
+    assert_eq!(to_json(f64::NAN, Null).unwrap(), json!(null));
+    assert_eq!(to_json(f64::INFINITY, Null).unwrap(), json!(null));
+    assert!(to_json(f64::NAN, Error).is_err());
+    assert!(to_json(f64::INFINITY, Error).is_err());
+    assert_eq!(to_json(f64::NAN, Tagged).unwrap(), json!({"$float": "NaN"}));
+    assert_eq!(
+        to_json(f64::INFINITY, Tagged).unwrap(),
+        json!({"$float": "Infinity"})
+    );
+    for policy in [Plain, Null, Error, Tagged] {
+        let j = to_json(-0.0, policy).unwrap();
+        assert!(j.as_f64().unwrap().is_sign_negative());
+    }
+
+    let opts = JsonOptions {
+        non_finite_floats: Tagged,
+        ..Default::default()
+    };
+    let l = DataValue::List(vec![
+        DataValue::from(f64::NEG_INFINITY),
+        DataValue::from(1.0),
+    ]);
+    let j = datavalue_to_json_with_opts(l.clone(), &opts).unwrap();
+    assert_eq!(j, json!([{"$float": "-Infinity"}, 1.0]));
+    assert_eq!(datavalue_from_json_with_opts(&j, &opts).unwrap(), l);
+    let nan = datavalue_from_json_with_opts(&json!({"$float": "NaN"}), &opts).unwrap();
+    assert!(nan.get_float().unwrap().is_nan());
+    assert!(datavalue_from_json_with_opts(&json!({"$float": "nan"}), &opts).is_err());
+
+    let vec = DataValue::Vec(Vector::F32(array![f32::NAN, 0.5]));
+    assert!(datavalue_to_json_with_opts(
+        vec,
+        &JsonOptions {
+            non_finite_floats: Error,
+            ..Default::default()
+        }
+    )
+    .is_err());
+}
+
+#[test]
+fn deeply_nested_json() {
+    fn array_depth(mut j: &JsonValue) -> usize {
+        let mut depth = 0;
+        while let JsonValue::Array(arr) = j {
+            depth += 1;
+            j = &arr[0];
+        }
+        depth
+    }
+
+    let mut deep = json!(1);
+    for _ in 0..10_000 {
+        deep = JsonValue::Array(vec![deep]);
+    }
+    assert!(DataValue::try_from_json_bounded(&deep, 128).is_err());
+
+    let json_below_128 = |v: &DataValue| {
+        let mut innermost = v;
+        for _ in 0..128 {
+            innermost = &innermost.get_slice().unwrap()[0];
+        }
+        matches!(innermost, DataValue::Json(_))
+    };
+    // the borrowing conversion clones the part kept as Json, which recurses
+    let mut less_deep = json!(1);
+    for _ in 0..1_000 {
+        less_deep = JsonValue::Array(vec![less_deep]);
+    }
+    let v = DataValue::from(&less_deep);
+    assert!(json_below_128(&v));
+    assert_eq!(array_depth(&JsonValue::from(v)), 1_000);
+    let v = datavalue_from_json_with_opts(&less_deep, &JsonOptions::default()).unwrap();
+    assert!(json_below_128(&v));
+    assert_eq!(v, DataValue::from(&less_deep));
+
+    let v = DataValue::from(deep);
+    assert!(json_below_128(&v));
+    assert_eq!(array_depth(&JsonValue::from(v)), 10_000);
+
+    let shallow = json!([[1], [[2]]]);
+    assert_eq!(
+        DataValue::try_from_json_bounded(&shallow, 3).unwrap(),
+        DataValue::from(&shallow)
+    );
+    assert!(DataValue::try_from_json_bounded(&shallow, 2).is_err());
+    assert_eq!(
+        DataValue::try_from_json_bounded(&json!("x"), 0).unwrap(),
+        DataValue::from("x")
+    );
+}
+
+#[test]
+fn iso8601_timestamps() {
+    let opts = JsonOptions {
+        timestamp_format: TimestampFormat::Iso8601,
+        ..Default::default()
+    };
+    let vld = |micros: i64, is_assert: bool| {
+        DataValue::Validity(Validity {
+            timestamp: ValidityTs(Reverse(micros)),
+            is_assert: Reverse(is_assert),
+        })
+    };
+    let typ = parse_type("Validity").unwrap();
+
+    let cases = [
+        (1_672_531_200_000_000, "2023-01-01T00:00:00Z"),
+        (1_672_531_200_123_456, "2023-01-01T00:00:00.123456Z"),
+        (1_672_531_200_500_000, "2023-01-01T00:00:00.500Z"),
+        (-1, "1969-12-31T23:59:59.999999Z"),
+    ];
+    for (micros, text) in cases {
+        let v = vld(micros, false);
+        let j = datavalue_to_json_with_opts(v.clone(), &opts).unwrap();
+        assert_eq!(j, json!([text, false]));
+        assert_eq!(DataValue::from_json_with_schema(&j, &typ).unwrap(), v);
+
+        let epoch = datavalue_to_json_with_opts(v.clone(), &JsonOptions::default()).unwrap();
+        assert_eq!(epoch, json!([micros, false]));
+        assert_eq!(DataValue::from_json_with_schema(&epoch, &typ).unwrap(), v);
+    }
+    assert_eq!(
+        DataValue::from_json_with_schema(&json!("2023-01-01T01:00:00.25+01:00"), &typ).unwrap(),
+        vld(1_672_531_200_250_000, true)
+    );
+    assert!(DataValue::from_json_with_schema(&json!(["yesterday", true]), &typ).is_err());
+}
+
+#[test]
+fn write_ndjson() {
+    let record = |id: i64, name: &str| {
+        DataValue::List(vec![
+            DataValue::List(vec![DataValue::from("id"), DataValue::from(id)]),
+            DataValue::List(vec![DataValue::from("name"), DataValue::from(name)]),
+        ])
+    };
+    let records = DataValue::List(vec![record(1, "a"), record(2, "line\nbreak")]);
+    let mut out = vec![];
+    records.write_ndjson(&mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let lines = text.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    for line in lines {
+        serde_json::from_str::<JsonValue>(line).unwrap();
+    }
+    assert!(text.ends_with('\n'));
+
+    let mut out = vec![];
+    DataValue::List(vec![]).write_ndjson(&mut out).unwrap();
+    assert!(out.is_empty());
+    assert!(DataValue::from(1).write_ndjson(&mut vec![]).is_err());
+}
@@ -135,3 +135,89 @@ fn encode_decode_datavalues() {
     assert!(remaining.is_empty());
     assert_eq!(decoded, v);
 }
+
+#[test]
+fn key_encoding_preserves_order() {
+    use rand::prelude::*;
+
+    fn random_value(rng: &mut impl Rng, depth: usize) -> DataValue {
+        match rng.gen_range(0..if depth > 2 { 8 } else { 9 }) {
+            0 => DataValue::Null,
+            1 => DataValue::from(rng.gen::<bool>()),
+            2 => DataValue::from(rng.gen_range(-5..5)),
+            3 => DataValue::from(
+                *[i64::MIN, i64::MAX, i64::MAX - 1, 1 << 53, (1 << 53) + 1]
+                    .choose(rng)
+                    .unwrap(),
+            ),
+            4 => DataValue::from(
+                *[
+                    -0.5,
+                    -0.0,
+                    0.0,
+                    1.0,
+                    9.007199254740992e15,
+                    f64::INFINITY,
+                    f64::NEG_INFINITY,
+                    f64::NAN,
+                ]
+                .choose(rng)
+                .unwrap(),
+            ),
+            5 => {
+                let len = rng.gen_range(0..12);
+                let s: String = (0..len)
+                    .map(|_| *['\0', 'a', 'b', 'é'].choose(rng).unwrap())
+                    .collect();
+                DataValue::from(s)
+            }
+            6 => DataValue::Bytes((0..rng.gen_range(0..12)).map(|_| rng.gen()).collect()),
+            7 => DataValue::uuid(Uuid::from_u128(rng.gen_range(0..4) << 100)),
+            _ => DataValue::List(
+                (0..rng.gen_range(0..4))
+                    .map(|_| random_value(rng, depth + 1))
+                    .collect(),
+            ),
+        }
+    }
+
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut values: Vec<_> = (0..2000).map(|_| random_value(&mut rng, 0)).collect();
+    values.push(DataValue::Validity((1, true).into()));
+    values.push(DataValue::Validity((1, false).into()));
+    values.push(DataValue::Validity((-1, true).into()));
+    values.push(DataValue::Bot);
+    for v in &values {
+        assert_eq!(&DataValue::decode_key(&v.encode_as_key()).unwrap(), v);
+    }
+    let mut by_value = values.clone();
+    by_value.sort();
+    let mut by_key = values.clone();
+    by_key.sort_by_key(|v| v.encode_as_key());
+    assert_eq!(by_value, by_key);
+
+    let mut descending = values;
+    descending.sort_by_key(|v| v.encode_as_key().iter().map(|b| !b).collect::<Vec<_>>());
+    by_value.reverse();
+    assert_eq!(by_value, descending);
+}
+
+#[test]
+fn decode_key_rejects_malformed() {
+    let key = DataValue::List(vec![DataValue::from("abc"), DataValue::from(1)]).encode_as_key();
+    for end in 0..key.len() {
+        assert!(DataValue::decode_key(&key[..end]).is_err());
+    }
+    let mut trailing = key.clone();
+    trailing.push(0);
+    assert!(DataValue::decode_key(&trailing).is_err());
+
+    let mut fractional = DataValue::from(1.5).encode_as_key();
+    *fractional.last_mut().unwrap() = 0;
+    assert!(DataValue::decode_key(&fractional).is_err());
+
+    let mut not_utf8 = DataValue::from("a").encode_as_key();
+    not_utf8[1] = 0xff;
+    assert!(DataValue::decode_key(&not_utf8).is_err());
+    assert!(DataValue::decode_key(&[0xAB]).is_err());
+}
@@ -7,9 +7,13 @@
  */
 
 mod aggrs;
+#[cfg(feature = "arrow")]
+mod arrow;
+mod coltypes;
 mod exprs;
 mod functions;
 mod json;
 mod memcmp;
 mod validity;
 mod values;
+mod wire;
@@ -7,11 +7,23 @@
  *
  */
 
+use std::borrow::Cow;
+use std::cmp::Reverse;
 use std::collections::{BTreeMap, HashMap};
 use std::mem::size_of;
 
+use ndarray::array;
+use regex::Regex;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::data::json::JsonValue;
 use crate::data::symb::Symbol;
-use crate::data::value::DataValue;
+use crate::data::value::{
+    DataValue, EqOptions, JsonData, Num, PathSeg, RegexWrapper, UuidWrapper, Validity, ValidityTs,
+    Vector,
+};
+use crate::parse::parse_expressions;
 
 #[test]
 fn show_size() {
@@ -55,3 +67,773 @@ fn display_datavalues() {
         ])
     );
 }
+
+#[test]
+fn chunk_list() {
+    let l = DataValue::List((0..6).map(DataValue::from).collect());
+    let chunked = l.chunk(2).unwrap();
+    assert_eq!(chunked.get_slice().unwrap().len(), 3);
+    assert_eq!(
+        chunked.get_slice().unwrap()[2],
+        DataValue::List(vec![DataValue::from(4), DataValue::from(5)])
+    );
+
+    let l = DataValue::List((0..5).map(DataValue::from).collect());
+    let chunked = l.chunk(2).unwrap();
+    let chunks = chunked.get_slice().unwrap();
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks[2], DataValue::List(vec![DataValue::from(4)]));
+
+    assert_eq!(l.chunk(0), None);
+    assert_eq!(DataValue::from(1).chunk(2), None);
+}
+
+#[test]
+fn dedup_consecutive() {
+    let l = DataValue::List(
+        [1, 1, 2, 3, 3, 3, 4]
+            .into_iter()
+            .map(DataValue::from)
+            .collect(),
+    );
+    assert_eq!(
+        l.dedup_consecutive().unwrap(),
+        DataValue::List([1, 2, 3, 4].into_iter().map(DataValue::from).collect())
+    );
+    assert_eq!(DataValue::from("a").dedup_consecutive(), None);
+}
+
+#[test]
+fn map_values() {
+    let m = DataValue::List(vec![
+        DataValue::List(vec![DataValue::from("a"), DataValue::from(1)]),
+        DataValue::List(vec![DataValue::from("b"), DataValue::from(2)]),
+    ]);
+    let incremented = m.map_values(|v| DataValue::from(v.get_int().unwrap() + 1));
+    assert_eq!(
+        incremented,
+        DataValue::List(vec![
+            DataValue::List(vec![DataValue::from("a"), DataValue::from(2)]),
+            DataValue::List(vec![DataValue::from("b"), DataValue::from(3)]),
+        ])
+    );
+    assert_eq!(
+        DataValue::from(1).map_values(|_| DataValue::Null),
+        DataValue::from(1)
+    );
+}
+
+#[test]
+fn eq_with_options() {
+    let exact = EqOptions::default();
+    let a = DataValue::List(vec![DataValue::from(1), DataValue::from(0.1 + 0.2)]);
+    let b = DataValue::List(vec![DataValue::from(1), DataValue::from(0.3)]);
+    let c = DataValue::List(vec![DataValue::from(1.0), DataValue::from(0.1 + 0.2)]);
+
+    assert!(a.eq_with(&a, &exact));
+    assert!(!a.eq_with(&b, &exact));
+    assert!(!a.eq_with(&c, &exact));
+
+    let tolerant = EqOptions {
+        float_epsilon: 1e-9,
+        ..Default::default()
+    };
+    assert!(a.eq_with(&b, &tolerant));
+    assert!(!a.eq_with(&c, &tolerant));
+
+    let numeric = EqOptions {
+        int_eq_float: true,
+        ..Default::default()
+    };
+    assert!(a.eq_with(&c, &numeric));
+    assert!(!a.eq_with(&b, &numeric));
+
+    let zero = DataValue::from(0.0);
+    let neg_zero = DataValue::from(-0.0);
+    assert_ne!(zero, neg_zero);
+    assert!(!zero.eq_with(&neg_zero, &exact));
+    assert!(zero.eq_with(&neg_zero, &tolerant));
+    let nan = DataValue::from(f64::NAN);
+    assert_eq!(nan, nan);
+    assert!(nan.eq_with(&nan, &exact));
+}
+
+#[test]
+fn flatten_map() {
+    let pair = |k: &str, v: DataValue| DataValue::List(vec![DataValue::from(k), v]);
+    let m = DataValue::List(vec![
+        pair(
+            "a",
+            DataValue::List(vec![
+                pair("b", DataValue::from(1)),
+                pair("c", DataValue::List(vec![DataValue::from(2)])),
+            ]),
+        ),
+        pair("d", DataValue::from(3)),
+    ]);
+    assert_eq!(
+        m.flatten_map("/").unwrap(),
+        DataValue::List(vec![
+            pair("a/b", DataValue::from(1)),
+            pair("a/c", DataValue::List(vec![DataValue::from(2)])),
+            pair("d", DataValue::from(3)),
+        ])
+    );
+    assert_eq!(DataValue::from(1).flatten_map("."), None);
+}
+
+#[test]
+fn sample() {
+    let l = DataValue::List((0..1000).map(DataValue::from).collect());
+    let s1 = l.sample(10, 42).unwrap();
+    let s2 = l.sample(10, 42).unwrap();
+    assert_eq!(s1, s2);
+    assert_eq!(s1.get_slice().unwrap().len(), 10);
+    assert_ne!(s1, l.sample(10, 43).unwrap());
+
+    let short = DataValue::List(vec![DataValue::from(1), DataValue::from(2)]);
+    assert_eq!(short.sample(10, 42).unwrap(), short);
+    assert_eq!(DataValue::from(1).sample(10, 42), None);
+}
+
+#[test]
+fn null_and_empty_list_order() {
+    let mut values = vec![
+        DataValue::List(vec![DataValue::from(0)]),
+        DataValue::List(vec![]),
+        DataValue::Null,
+    ];
+    values.sort();
+    assert_eq!(
+        values,
+        vec![
+            DataValue::Null,
+            DataValue::List(vec![]),
+            DataValue::List(vec![DataValue::from(0)]),
+        ]
+    );
+    assert_ne!(DataValue::Null, DataValue::List(vec![]));
+}
+
+#[test]
+fn total_order_across_variants() {
+    let kinds = [
+        DataValue::Null,
+        DataValue::from(true),
+        DataValue::from(1),
+        DataValue::from("a"),
+        DataValue::Bytes(vec![0]),
+        DataValue::uuid(Uuid::nil()),
+        DataValue::Regex(RegexWrapper(Regex::new("a").unwrap())),
+        DataValue::List(vec![]),
+        DataValue::Set(Default::default()),
+        DataValue::Vec(Vector::F32(array![0.])),
+        DataValue::Json(JsonData(json!(null))),
+        DataValue::Validity(Validity::from((0, true))),
+        DataValue::Bot,
+    ];
+    for (i, a) in kinds.iter().enumerate() {
+        for (j, b) in kinds.iter().enumerate() {
+            assert_eq!(a.cmp(b), i.cmp(&j), "{a:?} vs {b:?}");
+            assert_eq!(Reverse(a).cmp(&Reverse(b)), j.cmp(&i));
+        }
+    }
+}
+
+#[test]
+fn nan_order() {
+    let nan = DataValue::from(f64::NAN);
+    let mut values = [
+        nan.clone(),
+        DataValue::from(f64::INFINITY),
+        DataValue::from(i64::MAX),
+        DataValue::from(-f64::NAN),
+        DataValue::from(f64::NEG_INFINITY),
+        DataValue::from(0),
+    ];
+    values.sort();
+    assert_eq!(values.first(), Some(&DataValue::from(-f64::NAN)));
+    assert_eq!(values.last(), Some(&nan));
+    assert_eq!(values[1], DataValue::from(f64::NEG_INFINITY));
+    assert_eq!(nan.cmp(&nan), std::cmp::Ordering::Equal);
+    assert!(nan < DataValue::from(""));
+}
+
+#[test]
+fn content_hash() {
+    let mut m1 = serde_json::Map::new();
+    m1.insert("a".to_string(), json!(1));
+    m1.insert("b".to_string(), json!([1, 2]));
+    let mut m2 = serde_json::Map::new();
+    m2.insert("b".to_string(), json!([1, 2]));
+    m2.insert("a".to_string(), json!(1));
+    let v1 = DataValue::from(JsonValue::Object(m1));
+    let v2 = DataValue::from(JsonValue::Object(m2));
+    assert_eq!(v1, v2);
+    assert_eq!(v1.content_hash(), v2.content_hash());
+
+    let s1 = DataValue::Set([1, 2, 3].into_iter().map(DataValue::from).collect());
+    let s2 = DataValue::Set([3, 1, 2].into_iter().map(DataValue::from).collect());
+    assert_eq!(s1.content_hash(), s2.content_hash());
+
+    assert_ne!(
+        DataValue::from(1).content_hash(),
+        DataValue::from(1.0).content_hash()
+    );
+    assert_ne!(
+        DataValue::from("a").content_hash(),
+        DataValue::List(vec![DataValue::from("a")]).content_hash()
+    );
+}
+
+#[test]
+fn count_matching() {
+    let l = DataValue::List(vec![
+        DataValue::from(1),
+        DataValue::from("a"),
+        DataValue::from(2.5),
+        DataValue::List(vec![DataValue::from(3)]),
+        DataValue::from(4),
+    ]);
+    assert_eq!(
+        l.count_matching(|v| matches!(v, DataValue::Num(_))),
+        Some(3)
+    );
+    assert_eq!(
+        l.count_matching(|v| matches!(v, DataValue::Num(Num::Int(_)))),
+        Some(2)
+    );
+    assert_eq!(DataValue::from(1).count_matching(|_| true), None);
+}
+
+#[test]
+fn first_and_last() {
+    let l = DataValue::List(vec![
+        DataValue::from(1),
+        DataValue::from(2),
+        DataValue::from(3),
+    ]);
+    assert_eq!(l.first(), Some(&DataValue::from(1)));
+    assert_eq!(l.last(), Some(&DataValue::from(3)));
+
+    let empty = DataValue::List(vec![]);
+    assert_eq!(empty.first(), None);
+    assert_eq!(empty.last(), None);
+
+    assert_eq!(DataValue::from("abc").first(), None);
+    assert_eq!(DataValue::from("abc").last(), None);
+}
+
+#[test]
+fn variant_accessors() {
+    let id = Uuid::new_v4();
+    assert_eq!(DataValue::Uuid(UuidWrapper(id)).as_uuid(), Some(id));
+    assert_eq!(DataValue::from(id.to_string()).as_uuid(), None);
+
+    assert_eq!(
+        DataValue::Bytes(vec![1, 2]).get_bytes(),
+        Some([1u8, 2].as_slice())
+    );
+    assert_eq!(DataValue::from("\x01\x02").get_bytes(), None);
+
+    let ts = ValidityTs(Reverse(42));
+    let vld = DataValue::Validity(Validity {
+        timestamp: ts,
+        is_assert: Reverse(true),
+    });
+    assert_eq!(vld.as_timestamp(), Some(ts));
+    assert_eq!(DataValue::from(42).as_timestamp(), None);
+}
+
+#[test]
+fn contains() {
+    let l = DataValue::List(vec![DataValue::from(1), DataValue::from("a")]);
+    assert!(l.contains(&DataValue::from("a")));
+    assert!(!l.contains(&DataValue::from(2)));
+
+    let map = DataValue::List(vec![
+        DataValue::List(vec![DataValue::from("k"), DataValue::from(1)]),
+        DataValue::List(vec![DataValue::from("j"), DataValue::from(2)]),
+    ]);
+    assert!(map.contains(&DataValue::from("j")));
+    assert!(!map.contains(&DataValue::from(2)));
+
+    let obj = DataValue::from(&json!({"x": 1}));
+    assert!(obj.contains(&DataValue::from("x")));
+    assert!(!obj.contains(&DataValue::from("y")));
+
+    assert!(!DataValue::from("a").contains(&DataValue::from("a")));
+}
+
+#[test]
+fn rename_keys() {
+    let pair = |k: &str, v: i64| DataValue::List(vec![DataValue::from(k), DataValue::from(v)]);
+    let map = DataValue::List(vec![pair("a", 1), pair("b", 2)]);
+
+    let mapping = HashMap::from([("a".to_string(), "x".to_string())]);
+    assert_eq!(
+        map.clone().rename_keys(&mapping),
+        DataValue::List(vec![pair("x", 1), pair("b", 2)])
+    );
+
+    let colliding = HashMap::from([("a".to_string(), "b".to_string())]);
+    assert_eq!(
+        map.rename_keys(&colliding),
+        DataValue::List(vec![pair("b", 1), pair("b", 2)])
+    );
+
+    assert_eq!(
+        DataValue::from("a").rename_keys(&mapping),
+        DataValue::from("a")
+    );
+}
+
+#[test]
+fn split_pairs() {
+    let pair = |k: &str, v: i64| DataValue::List(vec![DataValue::from(k), DataValue::from(v)]);
+    let map = DataValue::List(vec![pair("a", 1), pair("b", 2)]);
+    assert_eq!(
+        map.split_pairs(),
+        Some((
+            DataValue::List(vec![DataValue::from("a"), DataValue::from("b")]),
+            DataValue::List(vec![DataValue::from(1), DataValue::from(2)]),
+        ))
+    );
+    assert_eq!(
+        DataValue::List(vec![]).split_pairs(),
+        Some((DataValue::List(vec![]), DataValue::List(vec![])))
+    );
+
+    let malformed = DataValue::List(vec![
+        pair("a", 1),
+        DataValue::List(vec![DataValue::from("b")]),
+    ]);
+    assert_eq!(malformed.split_pairs(), None);
+    assert_eq!(DataValue::from(1).split_pairs(), None);
+}
+
+#[test]
+fn coerce_keys_to_strings() {
+    let id = Uuid::new_v4();
+    let map = DataValue::List(vec![
+        DataValue::List(vec![DataValue::from(1), DataValue::from("a")]),
+        DataValue::List(vec![DataValue::from("k"), DataValue::from("b")]),
+        DataValue::List(vec![DataValue::Uuid(UuidWrapper(id)), DataValue::from("c")]),
+    ]);
+    assert_eq!(
+        map.coerce_keys_to_strings().unwrap(),
+        DataValue::List(vec![
+            DataValue::List(vec![DataValue::from("1"), DataValue::from("a")]),
+            DataValue::List(vec![DataValue::from("k"), DataValue::from("b")]),
+            DataValue::List(vec![DataValue::from(id.to_string()), DataValue::from("c")]),
+        ])
+    );
+
+    let list_key = DataValue::List(vec![DataValue::List(vec![
+        DataValue::List(vec![DataValue::from(1)]),
+        DataValue::from("a"),
+    ])]);
+    assert!(list_key.coerce_keys_to_strings().is_err());
+
+    assert_eq!(
+        DataValue::from(1).coerce_keys_to_strings().unwrap(),
+        DataValue::from(1)
+    );
+}
+
+#[test]
+fn entries() {
+    let pair = |k: &str, v: i64| DataValue::List(vec![DataValue::from(k), DataValue::from(v)]);
+    let map = DataValue::List(vec![pair("a", 1), pair("b", 2)]);
+    let collected = map.entries().unwrap().collect::<Vec<_>>();
+    assert_eq!(
+        collected,
+        vec![
+            (&DataValue::from("a"), &DataValue::from(1)),
+            (&DataValue::from("b"), &DataValue::from(2)),
+        ]
+    );
+
+    let malformed = DataValue::List(vec![pair("a", 1), DataValue::from(2)]);
+    assert!(malformed.entries().is_none());
+    assert!(DataValue::from(1).entries().is_none());
+}
+
+#[test]
+fn transpose() {
+    let row = |xs: &[i64]| DataValue::List(xs.iter().map(|x| DataValue::from(*x)).collect());
+    let m = DataValue::List(vec![row(&[1, 2, 3]), row(&[4, 5, 6])]);
+    assert_eq!(
+        m.transpose(),
+        Some(DataValue::List(vec![
+            row(&[1, 4]),
+            row(&[2, 5]),
+            row(&[3, 6])
+        ]))
+    );
+
+    let ragged = DataValue::List(vec![row(&[1, 2]), row(&[3])]);
+    assert_eq!(ragged.transpose(), None);
+    assert_eq!(row(&[1, 2]).transpose(), None);
+    assert_eq!(
+        DataValue::List(vec![]).transpose(),
+        Some(DataValue::List(vec![]))
+    );
+}
+
+#[test]
+fn tuple_conversions() {
+    let pair = (DataValue::from(1), DataValue::from("a"));
+    let v = DataValue::from(pair.clone());
+    assert_eq!(
+        v,
+        DataValue::List(vec![DataValue::from(1), DataValue::from("a")])
+    );
+    assert_eq!(<(DataValue, DataValue)>::try_from(v.clone()).unwrap(), pair);
+
+    let triple = (DataValue::Null, DataValue::from(2.5), DataValue::from(true));
+    let w = DataValue::from(triple.clone());
+    assert_eq!(
+        <(DataValue, DataValue, DataValue)>::try_from(w.clone()).unwrap(),
+        triple
+    );
+
+    assert!(<(DataValue, DataValue, DataValue)>::try_from(v).is_err());
+    assert!(<(DataValue, DataValue)>::try_from(w).is_err());
+    assert!(<(DataValue, DataValue)>::try_from(DataValue::from(1)).is_err());
+}
+
+#[test]
+fn windows() {
+    let row = |xs: &[i64]| DataValue::List(xs.iter().map(|x| DataValue::from(*x)).collect());
+    let l = row(&[1, 2, 3, 4]);
+    assert_eq!(
+        l.windows(2),
+        Some(DataValue::List(vec![
+            row(&[1, 2]),
+            row(&[2, 3]),
+            row(&[3, 4])
+        ]))
+    );
+    assert_eq!(l.windows(5), Some(DataValue::List(vec![])));
+    assert_eq!(l.windows(0), None);
+    assert_eq!(DataValue::from(1).windows(2), None);
+}
+
+#[test]
+fn serde_round_trip() {
+    let values = vec![
+        DataValue::Null,
+        DataValue::Bool(true),
+        DataValue::from(-7),
+        DataValue::from(2.5),
+        DataValue::from("text"),
+        DataValue::Bytes(vec![0, 1, 255]),
+        DataValue::Uuid(UuidWrapper(Uuid::new_v4())),
+        DataValue::Regex(RegexWrapper(Regex::new("a+b").unwrap())),
+        DataValue::List(vec![DataValue::from(1), DataValue::List(vec![])]),
+        DataValue::Set([DataValue::from(1), DataValue::from("a")].into()),
+        DataValue::Vec(Vector::F32(array![1.0, 2.0])),
+        DataValue::Vec(Vector::F64(array![0.5])),
+        DataValue::Json(JsonData(json!({"a": [1, "b"]}))),
+        DataValue::Validity(Validity {
+            timestamp: ValidityTs(Reverse(12)),
+            is_assert: Reverse(false),
+        }),
+        DataValue::Bot,
+    ];
+    for v in values {
+        let j = serde_json::to_string(&v).unwrap();
+        assert_eq!(serde_json::from_str::<DataValue>(&j).unwrap(), v);
+        let m = rmp_serde::to_vec(&v).unwrap();
+        assert_eq!(rmp_serde::from_slice::<DataValue>(&m).unwrap(), v);
+    }
+}
+
+#[test]
+fn as_scalar() {
+    let i = DataValue::from(1);
+    assert_eq!(i.as_scalar(), Some(&i));
+    assert_eq!(DataValue::Null.as_scalar(), Some(&DataValue::Null));
+    assert_eq!(DataValue::List(vec![i.clone()]).as_scalar(), None);
+    assert_eq!(DataValue::Set([i].into()).as_scalar(), None);
+    assert_eq!(DataValue::from(&json!({"a": 1})).as_scalar(), None);
+}
+
+#[test]
+fn json_pointer() {
+    let doc = DataValue::List(vec![
+        DataValue::List(vec![
+            DataValue::from("items"),
+            DataValue::List(vec![DataValue::from(10), DataValue::from(20)]),
+        ]),
+        DataValue::List(vec![DataValue::from("a/b"), DataValue::from(1)]),
+        DataValue::List(vec![DataValue::from("m~n"), DataValue::from(2)]),
+        DataValue::List(vec![
+            DataValue::from("obj"),
+            DataValue::from(&json!({"x": [true, {"y": "z"}]})),
+        ]),
+    ]);
+    let at = |path: &str| doc.pointer(path).map(Cow::into_owned);
+    assert_eq!(at(""), Some(doc.clone()));
+    assert_eq!(at("/items/1"), Some(DataValue::from(20)));
+    assert_eq!(at("/a~1b"), Some(DataValue::from(1)));
+    assert_eq!(at("/m~0n"), Some(DataValue::from(2)));
+    assert_eq!(at("/obj/x/1/y"), Some(DataValue::from("z")));
+    assert_eq!(at("/obj/x/0"), Some(DataValue::from(true)));
+    assert!(matches!(doc.pointer("/items"), Some(Cow::Borrowed(_))));
+    assert!(matches!(doc.pointer("/obj/x"), Some(Cow::Owned(_))));
+
+    assert_eq!(doc.pointer("/items/2"), None);
+    assert_eq!(doc.pointer("/items/01"), None);
+    assert_eq!(doc.pointer("/missing"), None);
+    assert_eq!(doc.pointer("items"), None);
+    assert_eq!(DataValue::from(1).pointer("/0"), None);
+}
+
+#[test]
+fn sum_and_avg() {
+    let ints = DataValue::List(vec![
+        DataValue::from(1),
+        DataValue::from(2),
+        DataValue::from(4),
+    ]);
+    assert_eq!(ints.sum(), Some(DataValue::from(7)));
+    assert_eq!(ints.avg(), Some(DataValue::from(7.0 / 3.0)));
+
+    let mixed = DataValue::List(vec![DataValue::from(1), DataValue::from(0.5)]);
+    assert_eq!(mixed.sum(), Some(DataValue::from(1.5)));
+    assert_eq!(mixed.avg(), Some(DataValue::from(0.75)));
+
+    let overflowing = DataValue::List(vec![DataValue::from(i64::MAX), DataValue::from(1)]);
+    assert_eq!(
+        overflowing.sum(),
+        Some(DataValue::from(i64::MAX as f64 + 1.0))
+    );
+
+    let empty = DataValue::List(vec![]);
+    assert_eq!(empty.sum(), Some(DataValue::from(0)));
+    assert_eq!(empty.avg(), None);
+
+    let with_str = DataValue::List(vec![DataValue::from(1), DataValue::from("2")]);
+    assert_eq!(with_str.sum(), None);
+    assert_eq!(with_str.avg(), None);
+    assert_eq!(DataValue::from(1).sum(), None);
+}
+
+#[test]
+fn try_map_leaves() {
+    let check_positive = |v: &DataValue| match v.get_int() {
+        Some(i) if i <= 0 => Err(format!("{i} is not positive")),
+        _ => Ok(v.clone()),
+    };
+    let good = DataValue::List(vec![
+        DataValue::from(1),
+        DataValue::List(vec![DataValue::from(2), DataValue::from("a")]),
+    ]);
+    assert_eq!(good.try_map_leaves(check_positive).unwrap(), good);
+
+    let bad = DataValue::List(vec![
+        DataValue::from(1),
+        DataValue::List(vec![DataValue::from(-3)]),
+    ]);
+    assert_eq!(
+        bad.try_map_leaves(check_positive).unwrap_err(),
+        "-3 is not positive"
+    );
+
+    let doubled = good
+        .try_map_leaves(|v| match v.get_int() {
+            Some(i) => Ok::<_, ()>(DataValue::from(i * 2)),
+            None => Ok(v.clone()),
+        })
+        .unwrap();
+    assert_eq!(
+        doubled,
+        DataValue::List(vec![
+            DataValue::from(2),
+            DataValue::List(vec![DataValue::from(4), DataValue::from("a")]),
+        ])
+    );
+}
+
+#[test]
+fn is_homogeneous_list() {
+    let ints = DataValue::List(vec![DataValue::from(1), DataValue::from(2)]);
+    assert!(ints.is_homogeneous_list());
+    let mixed = DataValue::List(vec![DataValue::from(1), DataValue::from("a")]);
+    assert!(!mixed.is_homogeneous_list());
+    let numbers = DataValue::List(vec![DataValue::from(1), DataValue::from(1.5)]);
+    assert!(!numbers.is_homogeneous_list());
+    let lists = DataValue::List(vec![ints, mixed]);
+    assert!(lists.is_homogeneous_list());
+    assert!(DataValue::List(vec![]).is_homogeneous_list());
+    assert!(!DataValue::from(1).is_homogeneous_list());
+}
+
+#[test]
+fn set_path() {
+    let pair = |k: &str, v: DataValue| DataValue::List(vec![DataValue::from(k), v]);
+    let doc = DataValue::List(vec![
+        pair(
+            "user",
+            DataValue::List(vec![pair("name", DataValue::from("a"))]),
+        ),
+        pair(
+            "tags",
+            DataValue::List(vec![DataValue::from("x"), DataValue::from("y")]),
+        ),
+    ]);
+    let key = |k: &str| PathSeg::Key(k.to_string());
+
+    let renamed = doc
+        .set_path(&[key("user"), key("name")], DataValue::from("b"))
+        .unwrap();
+    assert_eq!(
+        renamed.pointer("/user/name").as_deref(),
+        Some(&DataValue::from("b"))
+    );
+    assert_eq!(renamed.pointer("/tags"), doc.pointer("/tags"));
+
+    let retagged = doc
+        .set_path(&[key("tags"), PathSeg::Index(1)], DataValue::from("z"))
+        .unwrap();
+    assert_eq!(
+        retagged.pointer("/tags/1").as_deref(),
+        Some(&DataValue::from("z"))
+    );
+    assert_eq!(
+        retagged.pointer("/tags/0").as_deref(),
+        Some(&DataValue::from("x"))
+    );
+
+    assert_eq!(doc.set_path(&[], DataValue::Null), Some(DataValue::Null));
+    assert_eq!(
+        doc.set_path(&[key("user"), key("name"), key("first")], DataValue::Null),
+        None
+    );
+    assert_eq!(doc.set_path(&[key("missing")], DataValue::Null), None);
+    assert_eq!(
+        doc.set_path(&[key("tags"), PathSeg::Index(2)], DataValue::Null),
+        None
+    );
+
+    let j = DataValue::from(&json!({"a": [1, {"b": 2}]}));
+    let set = j
+        .set_path(&[key("a"), PathSeg::Index(1), key("b")], DataValue::from(3))
+        .unwrap();
+    assert_eq!(JsonValue::from(set), json!({"a": [1, {"b": 3}]}));
+    assert_eq!(j.set_path(&[key("c")], DataValue::Null), None);
+}
+
+#[test]
+fn join_strings() {
+    let l = DataValue::List(vec![
+        DataValue::from("a"),
+        DataValue::from("b"),
+        DataValue::from("c"),
+    ]);
+    assert_eq!(l.join_strings(", "), Some(DataValue::from("a, b, c")));
+    assert_eq!(l.join_strings(""), Some(DataValue::from("abc")));
+    assert_eq!(
+        DataValue::List(vec![]).join_strings("-"),
+        Some(DataValue::from(""))
+    );
+    let mixed = DataValue::List(vec![DataValue::from("a"), DataValue::from(1)]);
+    assert_eq!(mixed.join_strings(", "), None);
+    assert_eq!(DataValue::from("a").join_strings(", "), None);
+}
+
+#[test]
+fn as_accessors() {
+    assert_eq!(DataValue::from(1).as_int(), Some(1));
+    assert_eq!(DataValue::from(1.0).as_int(), None);
+    assert_eq!(DataValue::from(1.5).as_float(), Some(1.5));
+    assert_eq!(DataValue::from(1).as_float(), None);
+}
+
+#[test]
+fn script_literals() {
+    let id = Uuid::parse_str("dd85b19a-5fde-11ed-a88e-1774a7698039").unwrap();
+    let cases = [
+        (DataValue::Null, "null"),
+        (DataValue::Bot, "null"),
+        (DataValue::from(false), "false"),
+        (DataValue::from(-3), "-3"),
+        (DataValue::from(1.0), "1.0"),
+        (DataValue::from(-0.25), "-0.25"),
+        (DataValue::from(1e300), "1e300"),
+        (DataValue::from(f64::NAN), r#"to_float("NAN")"#),
+        (DataValue::from(f64::NEG_INFINITY), r#"to_float("NEG_INF")"#),
+        (DataValue::from("a\\b\né"), "\"a\\b\né\""),
+        (DataValue::from(r#"say "hi"_"#), r#"__"say "hi"_"__"#),
+        (DataValue::Bytes(vec![1, 2, 3]), r#"decode_base64("AQID")"#),
+        (
+            DataValue::uuid(id),
+            r#"to_uuid("dd85b19a-5fde-11ed-a88e-1774a7698039")"#,
+        ),
+        (
+            DataValue::Regex(RegexWrapper(Regex::new("a+").unwrap())),
+            r#"regex("a+")"#,
+        ),
+        (
+            DataValue::List(vec![DataValue::from(1), DataValue::List(vec![])]),
+            "[1, []]",
+        ),
+        (
+            DataValue::Vec(Vector::F32(array![1., 2.])),
+            "vec([1.0, 2.0])",
+        ),
+        (DataValue::Json(JsonData(json!({"a": 1}))), r#"{"a":1}"#),
+        (DataValue::Json(JsonData(json!([1]))), "json([1])"),
+    ];
+    for (v, expected) in cases {
+        assert_eq!(v.to_script_literal(), expected);
+    }
+
+    // Display is for messages, so strings stay escaped on one line
+    assert_eq!(DataValue::from("a\nb").to_string(), r#""a\nb""#);
+    assert_eq!(DataValue::from(1.0).to_string(), "1");
+}
+
+#[test]
+fn script_literals_reparse() {
+    let values = [
+        DataValue::Null,
+        DataValue::from(true),
+        DataValue::from(i64::MIN + 1),
+        DataValue::from(2.0),
+        DataValue::from(1e-7),
+        DataValue::from(f64::INFINITY),
+        DataValue::from(""),
+        DataValue::from("slash\\ tab\t bell\u{7} \u{301}"),
+        DataValue::from(r#"a"b"_"__c""#),
+        DataValue::Bytes(vec![0, 255, 7]),
+        DataValue::uuid(Uuid::from_u128(12345)),
+        DataValue::List(vec![DataValue::from("x"), DataValue::from(0.5)]),
+    ];
+    for v in values {
+        let expr = parse_expressions(&v.to_script_literal(), &BTreeMap::new()).unwrap();
+        // Int(2) and Float(2.0) are not equal, so this also checks the kind of number
+        assert_eq!(expr.eval(&[]).unwrap(), v);
+    }
+}
+
+#[test]
+fn timestamp_millis() {
+    for millis in [0, 1, -1, 1_700_000_000_123, -86_400_000] {
+        let ts = DataValue::timestamp_from_millis(millis);
+        assert_eq!(ts.timestamp_millis(), Some(millis));
+        assert_eq!(ts.as_timestamp(), Some(ValidityTs(Reverse(millis * 1000))));
+    }
+    assert_eq!(
+        DataValue::Validity((-1, true).into()).timestamp_millis(),
+        Some(-1)
+    );
+    assert_eq!(DataValue::from(1000).timestamp_millis(), None);
+
+    let earlier = DataValue::now_timestamp().timestamp_millis().unwrap();
+    let later = DataValue::now_timestamp().timestamp_millis().unwrap();
+    assert!(earlier <= later);
+    assert!(earlier > 1_600_000_000_000);
+}
@@ -0,0 +1,98 @@
+/*
+ * Copyright 2023, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::io::{Cursor, ErrorKind};
+
+use ndarray::array;
+use regex::Regex;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::data::value::{
+    DataValue, JsonData, RegexWrapper, UuidWrapper, Validity, ValidityTs, Vector,
+};
+
+fn round_trip(v: DataValue) {
+    let mut buf = vec![];
+    v.write_to(&mut buf).unwrap();
+    let mut cursor = Cursor::new(&buf);
+    let decoded = DataValue::read_from(&mut cursor).unwrap();
+    assert_eq!(decoded, v);
+    assert_eq!(cursor.position() as usize, buf.len());
+}
+
+#[test]
+fn wire_round_trip_scalars() {
+    round_trip(DataValue::Null);
+    round_trip(DataValue::Bool(false));
+    round_trip(DataValue::Bool(true));
+    round_trip(DataValue::from(-42));
+    round_trip(DataValue::from(i64::MAX));
+    round_trip(DataValue::from(1.5));
+    round_trip(DataValue::from(f64::NEG_INFINITY));
+    round_trip(DataValue::from(""));
+    round_trip(DataValue::from("héllo"));
+    round_trip(DataValue::Bytes(vec![0, 1, 255]));
+    round_trip(DataValue::Uuid(UuidWrapper(Uuid::new_v4())));
+    round_trip(DataValue::Regex(RegexWrapper(
+        Regex::new("^a+b?$").unwrap(),
+    )));
+    round_trip(DataValue::Json(JsonData(json!({"a": [1, null, "x"]}))));
+    round_trip(DataValue::Validity(Validity {
+        timestamp: ValidityTs(Reverse(-123)),
+        is_assert: Reverse(true),
+    }));
+    round_trip(DataValue::Bot);
+}
+
+#[test]
+fn wire_round_trip_collections() {
+    round_trip(DataValue::List(vec![]));
+    round_trip(DataValue::List(vec![
+        DataValue::from(1),
+        DataValue::List(vec![DataValue::from("a"), DataValue::Null]),
+    ]));
+    round_trip(DataValue::Set(BTreeSet::from([
+        DataValue::from(1),
+        DataValue::from("b"),
+    ])));
+    round_trip(DataValue::Vec(Vector::F32(array![1.0, -2.5])));
+    round_trip(DataValue::Vec(Vector::F64(array![0.25, 3.0, 1e100])));
+}
+
+#[test]
+fn wire_rejects_bad_input() {
+    let err = DataValue::read_from(&mut Cursor::new([0x7f])).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+    let mut buf = vec![];
+    DataValue::from("truncated").write_to(&mut buf).unwrap();
+    buf.truncate(buf.len() - 1);
+    let err = DataValue::read_from(&mut Cursor::new(&buf)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn wire_rejects_deep_nesting() {
+    let nested = |depth: usize| {
+        let mut buf = vec![];
+        for _ in 0..depth {
+            buf.push(0x09);
+            buf.extend_from_slice(&1u64.to_be_bytes());
+        }
+        buf.push(0x00);
+        buf
+    };
+    assert!(DataValue::read_from(&mut Cursor::new(nested(128))).is_ok());
+    let err = DataValue::read_from(&mut Cursor::new(nested(129))).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    let err = DataValue::read_from(&mut Cursor::new(nested(100_000))).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
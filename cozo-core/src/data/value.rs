@@ -9,15 +9,22 @@
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
 use ndarray::Array1;
+use std::borrow::Cow;
 use std::cmp::{Ordering, Reverse};
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 
+use crate::data::functions::current_validity;
 use crate::data::json::JsonValue;
+use crate::data::memcmp::MemCmpEncoder;
 use crate::data::relation::VecElementType;
+use itertools::Itertools;
+use miette::{bail, Diagnostic, Result};
 use ordered_float::OrderedFloat;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use regex::Regex;
 use serde::de::{SeqAccess, Visitor};
 use serde::ser::SerializeTuple;
@@ -25,6 +32,7 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use sha2::digest::FixedOutput;
 use sha2::{Digest, Sha256};
 use smartstring::{LazyCompact, SmartString};
+use thiserror::Error;
 use uuid::Uuid;
 
 /// UUID value in the database
@@ -59,20 +67,23 @@ impl Hash for RegexWrapper {
 }
 
 impl Serialize for RegexWrapper {
-    fn serialize<S>(&self, _serializer: S) -> std::result::Result<S::Ok, S::Error>
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
         where
             S: serde::Serializer,
     {
-        panic!("serializing regex");
+        serializer.serialize_str(self.0.as_str())
     }
 }
 
 impl<'de> Deserialize<'de> for RegexWrapper {
-    fn deserialize<D>(_deserializer: D) -> std::result::Result<Self, D::Error>
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
         where
             D: Deserializer<'de>,
     {
-        panic!("deserializing regex");
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern)
+            .map(RegexWrapper)
+            .map_err(serde::de::Error::custom)
     }
 }
 
@@ -140,6 +151,13 @@ impl From<(i64, bool)> for Validity {
 }
 
 /// A Value in the database
+///
+/// The order is total, also across variants: values of different variants are ordered as the
+/// variants are declared below, i.e. `Null < Bool < Num < Str < Bytes < Uuid < Regex < List <
+/// Set < Vec < Json < Validity < Bot`. Numbers compare by value regardless of being int or
+/// float, with an int sorting just before an equal float, and floats follow `f64::total_cmp`, so
+/// NaN has a fixed place. Lists are ordered lexicographically, hence the empty List sorts before
+/// every non-empty List. Wrap values in [std::cmp::Reverse] for descending order.
 #[derive(
 Clone, PartialEq, Eq, PartialOrd, Ord, serde_derive::Deserialize, serde_derive::Serialize, Hash,
 )]
@@ -275,7 +293,8 @@ impl<'de> Visitor<'de> for VectorVisitor {
         let tag: u8 = seq
             .next_element()?
             .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
-        let bytes: &[u8] = seq
+        // owned, so that formats without borrowed bytes such as JSON work too
+        let bytes: serde_bytes::ByteBuf = seq
             .next_element()?
             .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
         match tag {
@@ -468,6 +487,51 @@ impl From<bool> for DataValue {
     }
 }
 
+impl From<(DataValue, DataValue)> for DataValue {
+    fn from((a, b): (DataValue, DataValue)) -> Self {
+        DataValue::List(vec![a, b])
+    }
+}
+
+impl From<(DataValue, DataValue, DataValue)> for DataValue {
+    fn from((a, b, c): (DataValue, DataValue, DataValue)) -> Self {
+        DataValue::List(vec![a, b, c])
+    }
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("expected a list of length {0}, got {1:?}")]
+#[diagnostic(code(eval::bad_tuple_arity))]
+struct BadTupleArity(usize, DataValue);
+
+impl TryFrom<DataValue> for (DataValue, DataValue) {
+    type Error = miette::Error;
+
+    fn try_from(v: DataValue) -> Result<Self> {
+        match v {
+            DataValue::List(l) if l.len() == 2 => {
+                let [a, b]: [DataValue; 2] = l.try_into().unwrap();
+                Ok((a, b))
+            }
+            v => bail!(BadTupleArity(2, v)),
+        }
+    }
+}
+
+impl TryFrom<DataValue> for (DataValue, DataValue, DataValue) {
+    type Error = miette::Error;
+
+    fn try_from(v: DataValue) -> Result<Self> {
+        match v {
+            DataValue::List(l) if l.len() == 3 => {
+                let [a, b, c]: [DataValue; 3] = l.try_into().unwrap();
+                Ok((a, b, c))
+            }
+            v => bail!(BadTupleArity(3, v)),
+        }
+    }
+}
+
 /// Representing a number
 #[derive(Copy, Clone, serde_derive::Deserialize, serde_derive::Serialize)]
 pub enum Num {
@@ -627,7 +691,57 @@ impl Display for DataValue {
     }
 }
 
+/// Writes a string as a raw string literal, which needs no escapes. Double quotes inside are
+/// dealt with by fencing the literal with more underscores than follow any of them.
+fn write_raw_string(f: &mut Formatter<'_>, s: &str) -> std::fmt::Result {
+    let fence_len = s
+        .match_indices('"')
+        .map(|(i, _)| s[i + 1..].chars().take_while(|c| *c == '_').count() + 1)
+        .max()
+        .unwrap_or(0);
+    let fence = "_".repeat(fence_len);
+    write!(f, "{fence}\"{s}\"{fence}")
+}
+
+/// Writes a value as a CozoScript literal, see [DataValue::to_script_literal]
+struct ScriptLiteral<'a>(&'a DataValue);
+
+impl Display for ScriptLiteral<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            // `{n:?}` keeps the decimal point of whole floats, so that they are not read
+            // back as ints
+            DataValue::Num(Num::Float(n)) if n.is_finite() => write!(f, "{n:?}"),
+            DataValue::Str(s) => write_raw_string(f, s),
+            DataValue::List(l) => write_literal_list(f, l),
+            DataValue::Set(s) => write_literal_list(f, s),
+            v => write!(f, "{v}"),
+        }
+    }
+}
+
+fn write_literal_list<'a>(
+    f: &mut Formatter<'_>,
+    l: impl IntoIterator<Item = &'a DataValue>,
+) -> std::fmt::Result {
+    f.write_str("[")?;
+    for (i, el) in l.into_iter().enumerate() {
+        if i != 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", ScriptLiteral(el))?;
+    }
+    f.write_str("]")
+}
+
 impl DataValue {
+    /// This value as it would be written in a query, so that scalars and lists of them
+    /// read back as the same value: strings are raw string literals and floats keep their
+    /// decimal point. Unlike `Display`, which is meant for messages, strings are not
+    /// escaped, so they may span lines.
+    pub fn to_script_literal(&self) -> String {
+        ScriptLiteral(self).to_string()
+    }
     /// Returns a slice of bytes if this one is a Bytes
     pub fn get_bytes(&self) -> Option<&[u8]> {
         match self {
@@ -688,6 +802,536 @@ impl DataValue {
             _ => None,
         }
     }
+    /// Returns the int if this one is an Int. Unlike [DataValue::get_int], whole Floats
+    /// are not converted.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            DataValue::Num(Num::Int(i)) => Some(*i),
+            _ => None,
+        }
+    }
+    /// Returns the float if this one is a Float. Unlike [DataValue::get_float], Ints
+    /// are not converted.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            DataValue::Num(Num::Float(f)) => Some(*f),
+            _ => None,
+        }
+    }
+    /// Returns the UUID if this one is a Uuid. Unlike coercion, strings are not parsed.
+    pub fn as_uuid(&self) -> Option<Uuid> {
+        match self {
+            DataValue::Uuid(UuidWrapper(uuid)) => Some(*uuid),
+            _ => None,
+        }
+    }
+    /// Returns this one if it is a scalar, i.e. not a List, Set, Vec, or a Json array or object
+    pub fn as_scalar(&self) -> Option<&DataValue> {
+        match self {
+            DataValue::List(_) | DataValue::Set(_) | DataValue::Vec(_) => None,
+            DataValue::Json(JsonData(JsonValue::Array(_) | JsonValue::Object(_))) => None,
+            v => Some(v),
+        }
+    }
+    /// Returns the timestamp if this one is a Validity
+    pub fn as_timestamp(&self) -> Option<ValidityTs> {
+        match self {
+            DataValue::Validity(vld) => Some(vld.timestamp),
+            _ => None,
+        }
+    }
+    /// An asserting Validity at the current time, in microseconds like all Validity timestamps.
+    pub fn now_timestamp() -> DataValue {
+        DataValue::Validity(Validity {
+            timestamp: current_validity(),
+            is_assert: Reverse(true),
+        })
+    }
+    /// An asserting Validity at `millis` milliseconds since the UNIX epoch. Validity timestamps
+    /// are stored in microseconds, so times too far from the epoch to be represented are clamped.
+    pub fn timestamp_from_millis(millis: i64) -> DataValue {
+        DataValue::Validity((millis.saturating_mul(1000), true).into())
+    }
+    /// Returns the timestamp of a Validity in milliseconds since the UNIX epoch, rounding down.
+    pub fn timestamp_millis(&self) -> Option<i64> {
+        self.as_timestamp().map(|ts| ts.0 .0.div_euclid(1000))
+    }
+    /// Splits a List into a List of Lists each holding at most `size` elements.
+    /// Returns `None` if this one is not a List or if `size` is zero.
+    pub fn chunk(&self, size: usize) -> Option<DataValue> {
+        if size == 0 {
+            return None;
+        }
+        let l = self.get_slice()?;
+        Some(DataValue::List(
+            l.chunks(size)
+                .map(|c| DataValue::List(c.to_vec()))
+                .collect(),
+        ))
+    }
+    /// Returns a List of all overlapping sub-Lists of `size` consecutive elements,
+    /// which is empty if `size` exceeds the length.
+    /// Returns `None` if this one is not a List or if `size` is zero.
+    pub fn windows(&self, size: usize) -> Option<DataValue> {
+        if size == 0 {
+            return None;
+        }
+        let l = self.get_slice()?;
+        Some(DataValue::List(
+            l.windows(size)
+                .map(|w| DataValue::List(w.to_vec()))
+                .collect(),
+        ))
+    }
+    /// Collapses adjacent equal elements of a List, keeping the first of each run.
+    /// Only consecutive duplicates are removed, so sort the List first for full dedup.
+    /// Returns `None` if this one is not a List.
+    pub fn dedup_consecutive(&self) -> Option<DataValue> {
+        let mut l = self.get_slice()?.to_vec();
+        l.dedup();
+        Some(DataValue::List(l))
+    }
+    /// Applies `f` to every value of a map, i.e. a List of `[key, value]` Lists,
+    /// keeping the keys. Anything that is not shaped like a map is returned unchanged.
+    pub fn map_values(self, f: impl Fn(DataValue) -> DataValue) -> DataValue {
+        match self {
+            DataValue::List(l) if is_pair_list(&l) => DataValue::List(
+                l.into_iter()
+                    .map(|pair| match pair {
+                        DataValue::List(mut kv) => {
+                            let v = kv.pop().unwrap();
+                            kv.push(f(v));
+                            DataValue::List(kv)
+                        }
+                        _ => unreachable!(),
+                    })
+                    .collect(),
+            ),
+            v => v,
+        }
+    }
+    /// Applies `f` to every value that is not a List or Set, keeping the nesting,
+    /// and stops at the first error.
+    pub fn try_map_leaves<E>(
+        &self,
+        f: impl Fn(&DataValue) -> std::result::Result<DataValue, E>,
+    ) -> std::result::Result<DataValue, E> {
+        fn go<E>(
+            v: &DataValue,
+            f: &impl Fn(&DataValue) -> std::result::Result<DataValue, E>,
+        ) -> std::result::Result<DataValue, E> {
+            Ok(match v {
+                DataValue::List(l) => DataValue::List(l.iter().map(|el| go(el, f)).try_collect()?),
+                DataValue::Set(s) => DataValue::Set(s.iter().map(|el| go(el, f)).try_collect()?),
+                v => f(v)?,
+            })
+        }
+
+        go(self, &f)
+    }
+    /// Renames the string keys of a map, i.e. a List of `[key, value]` Lists, that appear
+    /// in `mapping`. Other keys are kept. A renamed key may end up equal to another key,
+    /// in which case both entries are kept in their original order.
+    /// Anything that is not shaped like a map is returned unchanged.
+    pub fn rename_keys(self, mapping: &HashMap<String, String>) -> DataValue {
+        match self {
+            DataValue::List(l) if is_pair_list(&l) => DataValue::List(
+                l.into_iter()
+                    .map(|pair| match pair {
+                        DataValue::List(mut kv) => {
+                            if let Some(renamed) = kv[0].get_str().and_then(|k| mapping.get(k)) {
+                                kv[0] = DataValue::from(renamed.as_str());
+                            }
+                            DataValue::List(kv)
+                        }
+                        _ => unreachable!(),
+                    })
+                    .collect(),
+            ),
+            v => v,
+        }
+    }
+    /// Looks up a nested value by an RFC 6901 JSON pointer such as `/foo/0/bar`.
+    /// Segments index into Lists by position, into maps (Lists of `[key, value]` Lists)
+    /// by string key, and into Json values as JSON. The empty pointer refers to this one.
+    /// Returns `None` if the pointer is malformed or any segment is missing.
+    /// The result borrows from this value, except for parts of Json values, which have to
+    /// be converted.
+    pub fn pointer(&self, path: &str) -> Option<Cow<'_, DataValue>> {
+        if path.is_empty() {
+            return Some(Cow::Borrowed(self));
+        }
+        let rest = path.strip_prefix('/')?;
+        let (raw_seg, tail) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, ""),
+        };
+        let seg = raw_seg.replace("~1", "/").replace("~0", "~");
+        let child = match self {
+            DataValue::Json(JsonData(j)) => {
+                return j.pointer(path).map(|v| Cow::Owned(DataValue::from(v)))
+            }
+            DataValue::List(l) if !l.is_empty() && is_pair_list(l) => l
+                .iter()
+                .map(|pair| pair.get_slice().unwrap())
+                .find(|kv| kv[0].get_str() == Some(&seg))
+                .map(|kv| &kv[1])
+                .or_else(|| json_pointer_index(&seg).and_then(|i| l.get(i)))?,
+            DataValue::List(l) => l.get(json_pointer_index(&seg)?)?,
+            _ => return None,
+        };
+        child.pointer(tail)
+    }
+    /// Returns a copy of this value with the element at `path` replaced by `value`.
+    /// Keys index into maps (Lists of `[key, value]` Lists) and JSON objects, indices
+    /// into Lists and JSON arrays. Nothing is created: returns `None` if the path does
+    /// not lead to an existing element.
+    pub fn set_path(&self, path: &[PathSeg], value: DataValue) -> Option<DataValue> {
+        let (seg, rest) = match path.split_first() {
+            None => return Some(value),
+            Some(split) => split,
+        };
+        match (self, seg) {
+            (DataValue::Json(JsonData(j)), _) => {
+                let mut j = j.clone();
+                let mut target = &mut j;
+                for seg in path {
+                    target = match seg {
+                        PathSeg::Key(k) => target.as_object_mut()?.get_mut(k)?,
+                        PathSeg::Index(i) => target.as_array_mut()?.get_mut(*i)?,
+                    };
+                }
+                *target = JsonValue::from(value);
+                Some(DataValue::Json(JsonData(j)))
+            }
+            (DataValue::List(l), PathSeg::Key(k)) if is_pair_list(l) => {
+                let pos = l
+                    .iter()
+                    .position(|pair| pair.get_slice().unwrap()[0].get_str() == Some(k))?;
+                let kv = l[pos].get_slice().unwrap();
+                let replaced = DataValue::List(vec![kv[0].clone(), kv[1].set_path(rest, value)?]);
+                let mut l = l.clone();
+                l[pos] = replaced;
+                Some(DataValue::List(l))
+            }
+            (DataValue::List(l), PathSeg::Index(i)) => {
+                let replaced = l.get(*i)?.set_path(rest, value)?;
+                let mut l = l.clone();
+                l[*i] = replaced;
+                Some(DataValue::List(l))
+            }
+            _ => None,
+        }
+    }
+    /// Transposes a List of equal-length Lists, so that rows become columns.
+    /// Returns `None` if this one is not a List of Lists or if the Lists differ in length.
+    pub fn transpose(&self) -> Option<DataValue> {
+        let rows = self
+            .get_slice()?
+            .iter()
+            .map(|row| row.get_slice())
+            .collect::<Option<Vec<_>>>()?;
+        let width = rows.first().map_or(0, |row| row.len());
+        if rows.iter().any(|row| row.len() != width) {
+            return None;
+        }
+        Some(DataValue::List(
+            (0..width)
+                .map(|i| DataValue::List(rows.iter().map(|row| row[i].clone()).collect()))
+                .collect(),
+        ))
+    }
+    /// Iterates over the `(key, value)` entries of a map, i.e. a List of `[key, value]` Lists.
+    /// The shape is checked up front: returns `None` if any element is not a pair,
+    /// or if this one is not a List.
+    pub fn entries(&self) -> Option<impl Iterator<Item = (&DataValue, &DataValue)>> {
+        let pairs = self.get_slice()?;
+        if !is_pair_list(pairs) {
+            return None;
+        }
+        Some(pairs.iter().map(|pair| {
+            let kv = pair.get_slice().unwrap();
+            (&kv[0], &kv[1])
+        }))
+    }
+    /// Splits a map, i.e. a List of `[key, value]` Lists, into a List of its keys
+    /// and a List of its values, in the same order.
+    /// Returns `None` if this one is not shaped like a map.
+    pub fn split_pairs(&self) -> Option<(DataValue, DataValue)> {
+        let pairs = self.get_slice()?;
+        if !is_pair_list(pairs) {
+            return None;
+        }
+        let (keys, values) = pairs
+            .iter()
+            .map(|pair| {
+                let kv = pair.get_slice().unwrap();
+                (kv[0].clone(), kv[1].clone())
+            })
+            .unzip();
+        Some((DataValue::List(keys), DataValue::List(values)))
+    }
+    /// Converts the keys of a map, i.e. a List of `[key, value]` Lists, into strings so
+    /// that it can be exported as a JSON object. Numbers, bools and UUIDs use their
+    /// display form. Errors on keys without a sensible string form, such as Lists.
+    /// Anything that is not shaped like a map is returned unchanged.
+    pub fn coerce_keys_to_strings(self) -> Result<DataValue> {
+        #[derive(Debug, Error, Diagnostic)]
+        #[error("cannot use {0} as a string key")]
+        #[diagnostic(code(eval::bad_string_key))]
+        struct BadStringKey(DataValue);
+
+        match self {
+            DataValue::List(l) if is_pair_list(&l) => Ok(DataValue::List(
+                l.into_iter()
+                    .map(|pair| match pair {
+                        DataValue::List(mut kv) => {
+                            kv[0] = match &kv[0] {
+                                DataValue::Str(_) => kv[0].clone(),
+                                DataValue::Num(Num::Int(i)) => DataValue::from(i.to_string()),
+                                DataValue::Num(Num::Float(f)) => DataValue::from(f.to_string()),
+                                DataValue::Bool(b) => DataValue::from(b.to_string()),
+                                DataValue::Uuid(u) => DataValue::from(u.0.to_string()),
+                                k => bail!(BadStringKey(k.clone())),
+                            };
+                            Ok(DataValue::List(kv))
+                        }
+                        _ => unreachable!(),
+                    })
+                    .collect::<Result<_>>()?,
+            )),
+            v => Ok(v),
+        }
+    }
+    /// Flattens nested maps (Lists of `[key, value]` Lists) into a single map whose keys
+    /// are the key paths joined by `separator`. Values that are not maps, including plain
+    /// Lists and empty Lists, are kept as they are. Returns `None` if this one is not a map.
+    pub fn flatten_map(&self, separator: &str) -> Option<DataValue> {
+        fn key_str(k: &DataValue) -> String {
+            match k.get_str() {
+                Some(s) => s.to_string(),
+                None => k.to_string(),
+            }
+        }
+        fn flatten_into(
+            pairs: &[DataValue],
+            prefix: &str,
+            separator: &str,
+            collected: &mut Vec<DataValue>,
+        ) {
+            for pair in pairs {
+                let kv = pair.get_slice().unwrap();
+                let key = if prefix.is_empty() {
+                    key_str(&kv[0])
+                } else {
+                    format!("{prefix}{separator}{}", key_str(&kv[0]))
+                };
+                match &kv[1] {
+                    DataValue::List(l) if !l.is_empty() && is_pair_list(l) => {
+                        flatten_into(l, &key, separator, collected)
+                    }
+                    v => collected.push(DataValue::List(vec![DataValue::from(key), v.clone()])),
+                }
+            }
+        }
+
+        let pairs = self.get_slice()?;
+        if !is_pair_list(pairs) {
+            return None;
+        }
+        let mut collected = vec![];
+        flatten_into(pairs, "", separator, &mut collected);
+        Some(DataValue::List(collected))
+    }
+    /// Hash of the content of this value that is stable across runs and platforms,
+    /// suitable as a key for caches. Equal values hash equally: JSON objects are hashed
+    /// with their keys in sorted order, and sets in element order.
+    pub fn content_hash(&self) -> u64 {
+        let mut encoded = vec![];
+        encoded.encode_datavalue(self);
+        let digest = Sha256::digest(&encoded);
+        u64::from_be_bytes(digest[..8].try_into().unwrap())
+    }
+    /// Picks up to `n` elements of a List by reservoir sampling, seeded with `seed`
+    /// so that the same seed always gives the same sample.
+    /// Returns `None` if this one is not a List.
+    pub fn sample(&self, n: usize, seed: u64) -> Option<DataValue> {
+        let l = self.get_slice()?;
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut reservoir = l.iter().take(n).cloned().collect_vec();
+        for (i, el) in l.iter().enumerate().skip(n) {
+            let j = rng.gen_range(0..=i);
+            if j < n {
+                reservoir[j] = el.clone();
+            }
+        }
+        Some(DataValue::List(reservoir))
+    }
+    /// Counts the elements of a List satisfying `pred`. Nested Lists are not descended into.
+    /// Returns `None` if this one is not a List.
+    pub fn count_matching(&self, pred: impl Fn(&DataValue) -> bool) -> Option<usize> {
+        Some(self.get_slice()?.iter().filter(|v| pred(v)).count())
+    }
+    /// Sums a List of numbers. The sum is an Int if all elements are Ints and it does not
+    /// overflow, otherwise a Float. The empty List sums to Int 0.
+    /// Returns `None` if this one is not a List or has elements that are not numbers.
+    pub fn sum(&self) -> Option<DataValue> {
+        let nums = self
+            .get_slice()?
+            .iter()
+            .map(|v| match v {
+                DataValue::Num(n) => Some(*n),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let int_sum = nums.iter().try_fold(0i64, |acc, n| match n {
+            Num::Int(i) => acc.checked_add(*i),
+            Num::Float(_) => None,
+        });
+        Some(match int_sum {
+            Some(i) => DataValue::from(i),
+            None => DataValue::from(nums.iter().map(|n| n.get_float()).sum::<f64>()),
+        })
+    }
+    /// Averages a List of numbers as a Float.
+    /// Returns `None` if this one is not a List, is empty, or has elements that are not numbers.
+    pub fn avg(&self) -> Option<DataValue> {
+        let len = self.get_slice()?.len();
+        if len == 0 {
+            return None;
+        }
+        let total = match self.sum()? {
+            DataValue::Num(n) => n.get_float(),
+            _ => unreachable!(),
+        };
+        Some(DataValue::from(total / len as f64))
+    }
+    /// Returns the first element of a List, or `None` if this one is empty or not a List.
+    pub fn first(&self) -> Option<&DataValue> {
+        self.get_slice()?.first()
+    }
+    /// Returns the last element of a List, or `None` if this one is empty or not a List.
+    pub fn last(&self) -> Option<&DataValue> {
+        self.get_slice()?.last()
+    }
+    /// Returns `true` if a List or Set has an element equal to `needle`, or if a map has
+    /// `needle` as a key. Maps are Lists of `[key, value]` Lists and JSON objects, whose
+    /// keys only match Str needles. Scalars never contain anything.
+    pub fn contains(&self, needle: &DataValue) -> bool {
+        match self {
+            DataValue::List(l) => {
+                l.contains(needle)
+                    || (is_pair_list(l) && l.iter().any(|kv| kv.get_slice().unwrap()[0] == *needle))
+            }
+            DataValue::Set(s) => s.contains(needle),
+            DataValue::Json(JsonData(JsonValue::Object(o))) => match needle {
+                DataValue::Str(k) => o.contains_key(k.as_str()),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+    /// Joins a List of strings with `sep` between them.
+    /// Returns `None` if this one is not a List or any element is not a string.
+    pub fn join_strings(&self, sep: &str) -> Option<DataValue> {
+        let strs = self
+            .get_slice()?
+            .iter()
+            .map(|v| v.get_str())
+            .collect::<Option<Vec<_>>>()?;
+        Some(DataValue::from(strs.join(sep)))
+    }
+    /// Whether this is a List whose elements are all of the same kind, where Ints and
+    /// Floats are different kinds and the contents of nested Lists are not compared.
+    /// The empty List is homogeneous, scalars are not.
+    pub fn is_homogeneous_list(&self) -> bool {
+        match self {
+            DataValue::List(l) => l.iter().map(value_kind).all_equal(),
+            _ => false,
+        }
+    }
+}
+
+/// Segment of a path for [DataValue::set_path]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSeg {
+    /// key of a map or JSON object
+    Key(String),
+    /// position in a List or JSON array
+    Index(usize),
+}
+
+/// Options for [DataValue::eq_with]
+#[derive(Debug, Clone, Default)]
+pub struct EqOptions {
+    /// Floats no further apart than this are considered equal
+    pub float_epsilon: f64,
+    /// Whether an int is equal to a float with the same numeric value
+    pub int_eq_float: bool,
+}
+
+impl DataValue {
+    /// Structural equality that is configurable for numbers, recursing into lists, sets and vectors.
+    /// With default options this is the same as `==`, so that as numbers `-0.0` and `0.0`
+    /// are only equal given a positive `float_epsilon`.
+    pub fn eq_with(&self, other: &DataValue, opts: &EqOptions) -> bool {
+        let floats_eq = |l: f64, r: f64| {
+            OrderedFloat(l) == OrderedFloat(r) || (l - r).abs() <= opts.float_epsilon
+        };
+        match (self, other) {
+            (DataValue::Num(Num::Float(l)), DataValue::Num(Num::Float(r))) => {
+                if opts.float_epsilon == 0. {
+                    l.total_cmp(r) == Ordering::Equal
+                } else {
+                    floats_eq(*l, *r)
+                }
+            }
+            (DataValue::Num(Num::Int(i)), DataValue::Num(Num::Float(f)))
+            | (DataValue::Num(Num::Float(f)), DataValue::Num(Num::Int(i))) => {
+                opts.int_eq_float && floats_eq(*i as f64, *f)
+            }
+            (DataValue::List(l), DataValue::List(r)) => {
+                l.len() == r.len() && l.iter().zip(r).all(|(a, b)| a.eq_with(b, opts))
+            }
+            (DataValue::Set(l), DataValue::Set(r)) => {
+                l.len() == r.len() && l.iter().zip(r).all(|(a, b)| a.eq_with(b, opts))
+            }
+            (DataValue::Vec(Vector::F32(l)), DataValue::Vec(Vector::F32(r))) => {
+                l.len() == r.len()
+                    && l.iter()
+                        .zip(r)
+                        .all(|(a, b)| floats_eq(*a as f64, *b as f64))
+            }
+            (DataValue::Vec(Vector::F64(l)), DataValue::Vec(Vector::F64(r))) => {
+                l.len() == r.len() && l.iter().zip(r).all(|(a, b)| floats_eq(*a, *b))
+            }
+            _ => self == other,
+        }
+    }
+}
+
+/// Array index in a JSON pointer segment, which must not have leading zeros
+fn json_pointer_index(seg: &str) -> Option<usize> {
+    if seg.is_empty()
+        || !seg.bytes().all(|b| b.is_ascii_digit())
+        || (seg.len() > 1 && seg.starts_with('0'))
+    {
+        return None;
+    }
+    seg.parse().ok()
+}
+
+fn value_kind(v: &DataValue) -> (std::mem::Discriminant<DataValue>, Option<bool>) {
+    let is_int = match v {
+        DataValue::Num(n) => Some(matches!(n, Num::Int(_))),
+        _ => None,
+    };
+    (std::mem::discriminant(v), is_int)
+}
+
+fn is_pair_list(l: &[DataValue]) -> bool {
+    l.iter()
+        .all(|el| matches!(el, DataValue::List(kv) if kv.len() == 2))
 }
 
 pub(crate) const LARGEST_UTF_CHAR: char = '\u{10ffff}';
@@ -0,0 +1,234 @@
+/*
+ * Copyright 2023, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::cmp::Reverse;
+use std::io;
+use std::io::{Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use ndarray::Array1;
+use regex::Regex;
+use uuid::Uuid;
+
+use crate::data::value::{
+    DataValue, JsonData, Num, RegexWrapper, UuidWrapper, Validity, ValidityTs, Vector,
+};
+
+const NULL_TAG: u8 = 0x00;
+const FALSE_TAG: u8 = 0x01;
+const TRUE_TAG: u8 = 0x02;
+const INT_TAG: u8 = 0x03;
+const FLOAT_TAG: u8 = 0x04;
+const STR_TAG: u8 = 0x05;
+const BYTES_TAG: u8 = 0x06;
+const UUID_TAG: u8 = 0x07;
+const REGEX_TAG: u8 = 0x08;
+const LIST_TAG: u8 = 0x09;
+const SET_TAG: u8 = 0x0A;
+const VEC_F32_TAG: u8 = 0x0B;
+const VEC_F64_TAG: u8 = 0x0C;
+const JSON_TAG: u8 = 0x0D;
+const VLD_TAG: u8 = 0x0E;
+const BOT_TAG: u8 = 0x0F;
+
+/// Lists and sets nested deeper than this are rejected when reading, as the input
+/// is untrusted and reading recurses
+const MAX_READ_DEPTH: usize = 128;
+
+fn write_len(w: &mut impl Write, len: usize) -> io::Result<()> {
+    w.write_u64::<BigEndian>(len as u64)
+}
+
+fn write_bytes(w: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    write_len(w, bytes.len())?;
+    w.write_all(bytes)
+}
+
+fn read_len(r: &mut impl Read) -> io::Result<usize> {
+    let len = r.read_u64::<BigEndian>()?;
+    usize::try_from(len).map_err(|_| invalid_data(format!("length {len} too large")))
+}
+
+fn read_bytes(r: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_len(r)?;
+    let mut buf = vec![];
+    r.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buf)
+}
+
+fn read_string(r: &mut impl Read) -> io::Result<String> {
+    String::from_utf8(read_bytes(r)?).map_err(invalid_data)
+}
+
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+impl DataValue {
+    /// Writes this value in a self-describing binary format meant for wire protocols:
+    /// a tag byte for the variant followed by its payload, with strings, bytes and
+    /// collections prefixed by their length. Unlike the key encoding used for storage,
+    /// the output does not sort in the same order as the values.
+    /// Read it back with [DataValue::read_from].
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        match self {
+            DataValue::Null => w.write_u8(NULL_TAG),
+            DataValue::Bool(false) => w.write_u8(FALSE_TAG),
+            DataValue::Bool(true) => w.write_u8(TRUE_TAG),
+            DataValue::Num(Num::Int(i)) => {
+                w.write_u8(INT_TAG)?;
+                w.write_i64::<BigEndian>(*i)
+            }
+            DataValue::Num(Num::Float(f)) => {
+                w.write_u8(FLOAT_TAG)?;
+                w.write_f64::<BigEndian>(*f)
+            }
+            DataValue::Str(s) => {
+                w.write_u8(STR_TAG)?;
+                write_bytes(w, s.as_bytes())
+            }
+            DataValue::Bytes(b) => {
+                w.write_u8(BYTES_TAG)?;
+                write_bytes(w, b)
+            }
+            DataValue::Uuid(u) => {
+                w.write_u8(UUID_TAG)?;
+                w.write_all(u.0.as_bytes())
+            }
+            DataValue::Regex(rx) => {
+                w.write_u8(REGEX_TAG)?;
+                write_bytes(w, rx.0.as_str().as_bytes())
+            }
+            DataValue::List(l) => {
+                w.write_u8(LIST_TAG)?;
+                write_len(w, l.len())?;
+                for el in l {
+                    el.write_to(w)?;
+                }
+                Ok(())
+            }
+            DataValue::Set(s) => {
+                w.write_u8(SET_TAG)?;
+                write_len(w, s.len())?;
+                for el in s {
+                    el.write_to(w)?;
+                }
+                Ok(())
+            }
+            DataValue::Vec(Vector::F32(a)) => {
+                w.write_u8(VEC_F32_TAG)?;
+                write_len(w, a.len())?;
+                for el in a {
+                    w.write_f32::<BigEndian>(*el)?;
+                }
+                Ok(())
+            }
+            DataValue::Vec(Vector::F64(a)) => {
+                w.write_u8(VEC_F64_TAG)?;
+                write_len(w, a.len())?;
+                for el in a {
+                    w.write_f64::<BigEndian>(*el)?;
+                }
+                Ok(())
+            }
+            DataValue::Json(j) => {
+                w.write_u8(JSON_TAG)?;
+                write_bytes(w, j.0.to_string().as_bytes())
+            }
+            DataValue::Validity(vld) => {
+                w.write_u8(VLD_TAG)?;
+                w.write_i64::<BigEndian>(vld.timestamp.0 .0)?;
+                w.write_u8(vld.is_assert.0 as u8)
+            }
+            DataValue::Bot => w.write_u8(BOT_TAG),
+        }
+    }
+    /// Reads a value written by [DataValue::write_to].
+    /// Lists and sets nested more than 128 levels deep are rejected as invalid data.
+    pub fn read_from(r: &mut impl Read) -> io::Result<DataValue> {
+        DataValue::read_nested(r, 0)
+    }
+    fn read_nested(r: &mut impl Read, depth: usize) -> io::Result<DataValue> {
+        let tag = r.read_u8()?;
+        Ok(match tag {
+            NULL_TAG => DataValue::Null,
+            FALSE_TAG => DataValue::Bool(false),
+            TRUE_TAG => DataValue::Bool(true),
+            INT_TAG => DataValue::from(r.read_i64::<BigEndian>()?),
+            FLOAT_TAG => DataValue::from(r.read_f64::<BigEndian>()?),
+            STR_TAG => DataValue::from(read_string(r)?),
+            BYTES_TAG => DataValue::Bytes(read_bytes(r)?),
+            UUID_TAG => {
+                let mut buf = [0u8; 16];
+                r.read_exact(&mut buf)?;
+                DataValue::Uuid(UuidWrapper(Uuid::from_bytes(buf)))
+            }
+            REGEX_TAG => {
+                let rx = Regex::new(&read_string(r)?).map_err(invalid_data)?;
+                DataValue::Regex(RegexWrapper(rx))
+            }
+            LIST_TAG | SET_TAG if depth >= MAX_READ_DEPTH => {
+                return Err(invalid_data(format!(
+                    "values nested more than {MAX_READ_DEPTH} levels deep"
+                )))
+            }
+            LIST_TAG => {
+                let len = read_len(r)?;
+                let mut l = vec![];
+                for _ in 0..len {
+                    l.push(DataValue::read_nested(r, depth + 1)?);
+                }
+                DataValue::List(l)
+            }
+            SET_TAG => {
+                let len = read_len(r)?;
+                DataValue::Set(
+                    (0..len)
+                        .map(|_| DataValue::read_nested(r, depth + 1))
+                        .collect::<io::Result<_>>()?,
+                )
+            }
+            VEC_F32_TAG => {
+                let len = read_len(r)?;
+                let mut v = vec![];
+                for _ in 0..len {
+                    v.push(r.read_f32::<BigEndian>()?);
+                }
+                DataValue::Vec(Vector::F32(Array1::from(v)))
+            }
+            VEC_F64_TAG => {
+                let len = read_len(r)?;
+                let mut v = vec![];
+                for _ in 0..len {
+                    v.push(r.read_f64::<BigEndian>()?);
+                }
+                DataValue::Vec(Vector::F64(Array1::from(v)))
+            }
+            JSON_TAG => {
+                let j = serde_json::from_str(&read_string(r)?).map_err(invalid_data)?;
+                DataValue::Json(JsonData(j))
+            }
+            VLD_TAG => {
+                let ts = r.read_i64::<BigEndian>()?;
+                let is_assert = r.read_u8()? != 0;
+                DataValue::Validity(Validity {
+                    timestamp: ValidityTs(Reverse(ts)),
+                    is_assert: Reverse(is_assert),
+                })
+            }
+            BOT_TAG => DataValue::Bot,
+            t => return Err(invalid_data(format!("unknown value tag {t:#04x}"))),
+        })
+    }
+}
@@ -104,7 +104,9 @@ impl FixedRule for CsvReader {
                     Some(s) => {
                         let dv = DataValue::from(s);
                         match &typ.coltype {
-                            ColType::Any | ColType::String => out_tuple.push(dv),
+                            ColType::Any | ColType::String | ColType::BoundedString { .. } => {
+                                out_tuple.push(dv)
+                            }
                             ColType::Uuid => out_tuple.push(match op_to_uuid(&[dv]) {
                                 Ok(uuid) => uuid,
                                 Err(err) => {
@@ -115,16 +117,18 @@ impl FixedRule for CsvReader {
                                     }
                                 }
                             }),
-                            ColType::Float => out_tuple.push(match op_to_float(&[dv]) {
-                                Ok(data) => data,
-                                Err(err) => {
-                                    if typ.nullable {
-                                        DataValue::Null
-                                    } else {
-                                        bail!(err)
+                            ColType::Float | ColType::Float32 => {
+                                out_tuple.push(match op_to_float(&[dv]) {
+                                    Ok(data) => data,
+                                    Err(err) => {
+                                        if typ.nullable {
+                                            DataValue::Null
+                                        } else {
+                                            bail!(err)
+                                        }
                                     }
-                                }
-                            }),
+                                })
+                            }
                             ColType::Int => {
                                 let f = op_to_float(&[dv]).unwrap_or(DataValue::Null);
                                 match f.get_int() {
@@ -48,7 +48,10 @@ use miette::{
 };
 use serde_json::json;
 
-pub use data::value::{DataValue, Num, RegexWrapper, UuidWrapper, Validity, ValidityTs};
+pub use data::relation::{CoerceOptions, ColType, NullableColType, TypingCache, VecElementType};
+pub use data::value::{
+    DataValue, EqOptions, Num, PathSeg, RegexWrapper, UuidWrapper, Validity, ValidityTs,
+};
 pub use fixed_rule::{FixedRule, FixedRuleInputRelation, FixedRulePayload};
 pub use runtime::db::Db;
 pub use runtime::db::NamedRows;
@@ -67,6 +70,10 @@ pub use storage::{Storage, StoreTx};
 
 pub use crate::data::expr::Expr;
 use crate::data::json::JsonValue;
+pub use crate::data::json::{
+    datavalue_from_json_with_opts, datavalue_to_json_with_opts, JsonOptions, NonFiniteFloatPolicy,
+    TimestampFormat,
+};
 pub use crate::data::symb::Symbol;
 pub use crate::data::value::{JsonData, Vector};
 pub use crate::fixed_rule::SimpleFixedRule;
@@ -12,7 +12,7 @@ use std::fmt::{Display, Formatter};
 use std::sync::Arc;
 
 use either::{Either, Left};
-use miette::{bail, Diagnostic, IntoDiagnostic, Result};
+use miette::{bail, ensure, Diagnostic, IntoDiagnostic, Result};
 use pest::error::InputLocation;
 use pest::Parser;
 use smartstring::{LazyCompact, SmartString};
@@ -24,7 +24,7 @@ use crate::data::value::{DataValue, ValidityTs};
 use crate::parse::expr::build_expr;
 use crate::parse::imperative::parse_imperative_block;
 use crate::parse::query::parse_query;
-use crate::parse::schema::parse_nullable_type;
+use crate::parse::schema::{parse_nullable_type, TypeTooDeep};
 use crate::parse::sys::{parse_sys, SysOp};
 use crate::{Expr, FixedRule};
 
@@ -246,18 +246,96 @@ pub(crate) struct ParseError {
     pub(crate) span: SourceSpan,
 }
 
+/// Types nested deeper than this are rejected, as the parser would overflow the stack
+/// on pathological input
+pub(crate) const MAX_TYPE_NESTING: usize = 128;
+
+/// Scripts with brackets nested deeper than this are rejected before parsing, for the same
+/// reason. It leaves room for a type nested as deep as allowed inside a schema or a block.
+const MAX_SCRIPT_NESTING: usize = MAX_TYPE_NESTING + 32;
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("Script is nested more than {0} levels deep")]
+#[diagnostic(code(parser::script_too_deep))]
+struct ScriptTooDeep(usize);
+
+/// Maximum nesting of brackets in `src`, skipping over strings and comments. It is cheap to
+/// compute before parsing, which is where deeply nested input would overflow the stack.
+fn bracket_depth(src: &str) -> usize {
+    let src = src.as_bytes();
+    let mut depth = 0usize;
+    let mut max_depth = 0;
+    let mut i = 0;
+    while i < src.len() {
+        match src[i] {
+            b'[' | b'(' | b'{' => {
+                depth += 1;
+                max_depth = max_depth.max(depth);
+            }
+            b']' | b')' | b'}' => depth = depth.saturating_sub(1),
+            b'"' => {
+                // a raw string ends at a quote followed by the underscores opening it
+                let fence = src[..i].iter().rev().take_while(|c| **c == b'_').count();
+                i += 1;
+                while i < src.len()
+                    && !(src[i] == b'"'
+                        && src[i + 1..].iter().take_while(|c| **c == b'_').count() >= fence)
+                {
+                    i += 1;
+                }
+            }
+            b'\'' => {
+                i += 1;
+                while i < src.len() && src[i] != b'\'' {
+                    if src[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            b'#' => {
+                while i < src.len() && src[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if src.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < src.len() && !src[i..].starts_with(b"*/") {
+                    i += 1;
+                }
+                i += 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    max_depth
+}
+
 pub(crate) fn parse_type(src: &str) -> Result<NullableColType> {
+    parse_type_with_max_nesting(src, MAX_TYPE_NESTING)
+}
+
+pub(crate) fn parse_type_with_max_nesting(
+    src: &str,
+    max_nesting: usize,
+) -> Result<NullableColType> {
+    ensure!(bracket_depth(src) <= max_nesting, TypeTooDeep(max_nesting));
     let parsed = CozoScriptParser::parse(Rule::col_type_with_term, src)
         .into_diagnostic()?
         .next()
         .unwrap();
-    parse_nullable_type(parsed.into_inner().next().unwrap())
+    parse_nullable_type(parsed.into_inner().next().unwrap(), 0, max_nesting)
 }
 
 pub(crate) fn parse_expressions(
     src: &str,
     param_pool: &BTreeMap<String, DataValue>,
 ) -> Result<Expr> {
+    ensure!(
+        bracket_depth(src) <= MAX_SCRIPT_NESTING,
+        ScriptTooDeep(MAX_SCRIPT_NESTING)
+    );
     let parsed = CozoScriptParser::parse(Rule::expression_script, src)
         .map_err(|err| {
             let span = match err.location {
@@ -278,6 +356,10 @@ pub(crate) fn parse_script(
     fixed_rules: &BTreeMap<String, Arc<Box<dyn FixedRule>>>,
     cur_vld: ValidityTs,
 ) -> Result<CozoScript> {
+    ensure!(
+        bracket_depth(src) <= MAX_SCRIPT_NESTING,
+        ScriptTooDeep(MAX_SCRIPT_NESTING)
+    );
     let parsed = CozoScriptParser::parse(Rule::script, src)
         .map_err(|err| {
             let span = match err.location {
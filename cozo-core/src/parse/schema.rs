@@ -9,15 +9,15 @@
 use std::collections::BTreeSet;
 
 use itertools::Itertools;
-use miette::{bail, ensure, Diagnostic, Result, IntoDiagnostic};
+use miette::{bail, ensure, Diagnostic, IntoDiagnostic, Result};
 use smartstring::SmartString;
 use thiserror::Error;
 
 use crate::data::relation::{VecElementType, ColType, ColumnDef, NullableColType, StoredRelationMetadata};
 use crate::data::symb::Symbol;
 use crate::data::value::DataValue;
-use crate::parse::expr::{build_expr};
-use crate::parse::{ExtractSpan, Pair, Rule, SourceSpan};
+use crate::parse::expr::{build_expr, parse_string};
+use crate::parse::{ExtractSpan, Pair, Rule, SourceSpan, MAX_TYPE_NESTING};
 
 pub(crate) fn parse_schema(
     pair: Pair<'_>,
@@ -76,7 +76,7 @@ fn parse_col(pair: Pair<'_>) -> Result<(ColumnDef, Symbol)> {
     let mut binding_candidate = None;
     for nxt in src {
         match nxt.as_rule() {
-            Rule::col_type => typing = parse_nullable_type(nxt)?,
+            Rule::col_type => typing = parse_nullable_type(nxt, 0, MAX_TYPE_NESTING)?,
             Rule::expr => default_gen = Some(build_expr(nxt, &Default::default())?),
             Rule::out_arg => {
                 binding_candidate = Some(Symbol::new(nxt.as_str(), nxt.extract_span()))
@@ -96,28 +96,106 @@ fn parse_col(pair: Pair<'_>) -> Result<(ColumnDef, Symbol)> {
     ))
 }
 
-pub(crate) fn parse_nullable_type(pair: Pair<'_>) -> Result<NullableColType> {
-    let nullable = pair.as_str().ends_with('?');
-    let coltype = parse_type_inner(pair.into_inner().next().unwrap())?;
+#[derive(Debug, Error, Diagnostic)]
+#[error("Type is nested more than {0} levels deep")]
+#[diagnostic(code(parser::type_too_deep))]
+pub(crate) struct TypeTooDeep(pub(crate) usize);
+
+/// Parses a type found at nesting level `depth` of lists and tuples, failing if
+/// the type nests deeper than `max_nesting`
+pub(crate) fn parse_nullable_type(
+    pair: Pair<'_>,
+    depth: usize,
+    max_nesting: usize,
+) -> Result<NullableColType> {
+    let mut members: Vec<_> = pair
+        .into_inner()
+        .map(|p| parse_union_member(p, depth, max_nesting))
+        .try_collect()?;
+    Ok(if members.len() == 1 {
+        members.pop().unwrap()
+    } else {
+        NullableColType::union(members)
+    })
+}
+
+fn parse_union_member(pair: Pair<'_>, depth: usize, max_nesting: usize) -> Result<NullableColType> {
+    let src = pair.as_str();
+    let nullable = src.starts_with('?') || src.ends_with('?');
+    let inner = pair.into_inner().next().unwrap();
+    let fragment = inner.as_str();
+    let coltype = parse_type_inner(inner, depth, max_nesting).map_err(|err| {
+        // the fragments of a type too deep are not helpful, and there would be many of them
+        if err.is::<TypeTooDeep>() {
+            err
+        } else {
+            err.wrap_err(format!("when parsing type `{fragment}`"))
+        }
+    })?;
     Ok(NullableColType { coltype, nullable })
 }
 
-fn parse_type_inner(pair: Pair<'_>) -> Result<ColType> {
+fn parse_type_inner(pair: Pair<'_>, depth: usize, max_nesting: usize) -> Result<ColType> {
     Ok(match pair.as_rule() {
         Rule::any_type => ColType::Any,
         Rule::bool_type => ColType::Bool,
         Rule::int_type => ColType::Int,
         Rule::float_type => ColType::Float,
+        Rule::float32_type => ColType::Float32,
         Rule::string_type => ColType::String,
+        Rule::bounded_string_type => {
+            let max_len = pair.into_inner().next().unwrap();
+            let max_len = max_len.as_str().replace('_', "").parse::<usize>().into_diagnostic()?;
+            ColType::BoundedString { max_len }
+        }
+        Rule::quantity_type => {
+            let mut inner = pair.into_inner();
+            let base = parse_type_inner(inner.next().unwrap(), depth, max_nesting)?;
+            let unit = parse_string(inner.next().unwrap())?;
+            ColType::Quantity {
+                base: base.into(),
+                unit: unit.to_string(),
+            }
+        }
         Rule::bytes_type => ColType::Bytes,
         Rule::uuid_type => ColType::Uuid,
         Rule::json_type => ColType::Json,
         Rule::validity_type => ColType::Validity,
         Rule::list_type => {
+            ensure!(depth < max_nesting, TypeTooDeep(max_nesting));
             let mut inner = pair.into_inner();
-            let eltype = parse_nullable_type(inner.next().unwrap())?;
+            let eltype = parse_nullable_type(inner.next().unwrap(), depth + 1, max_nesting)?;
             let len = match inner.next() {
                 None => None,
+                Some(bounds_p) if bounds_p.as_rule() == Rule::list_len_bounds => {
+                    let mut min_len = 0;
+                    let mut max_len = None;
+                    for bound in bounds_p.into_inner() {
+                        let n = bound
+                            .as_str()
+                            .replace('_', "")
+                            .parse::<usize>()
+                            .into_diagnostic()?;
+                        match bound.as_rule() {
+                            Rule::list_min_len => min_len = n,
+                            Rule::list_max_len => max_len = Some(n),
+                            _ => unreachable!(),
+                        }
+                    }
+                    if let Some(max_len) = max_len {
+                        #[derive(Debug, Error, Diagnostic)]
+                        #[error("Bad list length bounds in type: {0} is more than {1}")]
+                        #[diagnostic(code(parser::bad_list_len_bounds_in_type))]
+                        struct BadListLenBounds(usize, usize);
+
+                        ensure!(min_len <= max_len, BadListLenBounds(min_len, max_len));
+                    }
+                    return Ok(ColType::BoundedList {
+                        eltype: eltype.into(),
+                        min_len,
+                        max_len,
+                    });
+                }
                 Some(len_p) => {
                     let span = len_p.extract_span();
                     let expr = build_expr(len_p, &Default::default())?;
@@ -153,8 +231,61 @@ fn parse_type_inner(pair: Pair<'_>) -> Result<ColType> {
             }
         }
         Rule::tuple_type => {
-            ColType::Tuple(pair.into_inner().map(parse_nullable_type).try_collect()?)
+            ensure!(depth < max_nesting, TypeTooDeep(max_nesting));
+            ColType::Tuple(
+                pair.into_inner()
+                    .map(|p| parse_nullable_type(p, depth + 1, max_nesting))
+                    .try_collect()?,
+            )
         }
         _ => unreachable!(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::{parse_type, parse_type_with_max_nesting};
+
+    #[test]
+    fn test_type_error_fragment() {
+        let err = parse_type("([Int; 1 + 'a'], Int)").unwrap_err();
+        let messages = err.chain().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert!(messages
+            .iter()
+            .any(|m| m.contains("when parsing type `[Int; 1 + 'a']`")));
+        assert!(messages
+            .iter()
+            .any(|m| m.contains("when parsing type `([Int; 1 + 'a'], Int)`")));
+    }
+
+    #[test]
+    fn test_prefix_nullable() {
+        assert_eq!(
+            parse_type("?String").unwrap(),
+            parse_type("String?").unwrap()
+        );
+        assert_eq!(
+            parse_type("[?Int; 2]").unwrap(),
+            parse_type("[Int?; 2]").unwrap()
+        );
+        assert!(parse_type("?Int").unwrap().nullable);
+        assert!(!parse_type("(?Int, Bool)").unwrap().nullable);
+        assert!(parse_type("?Int?").is_err());
+    }
+
+    #[test]
+    fn test_deeply_nested_type() {
+        let nested = |n: usize| format!("{}Int{}", "[?".repeat(n), "]".repeat(n));
+        assert!(parse_type(&nested(100)).is_ok());
+        for n in [150, 10_000] {
+            let err = parse_type(&nested(n)).unwrap_err();
+            assert_eq!(err.to_string(), "Type is nested more than 128 levels deep");
+        }
+
+        assert!(parse_type_with_max_nesting(&nested(3), 3).is_ok());
+        assert!(parse_type_with_max_nesting(&nested(4), 3).is_err());
+        assert!(parse_type_with_max_nesting("(Int, <F32; 2>)", 1).is_ok());
+        assert!(parse_type_with_max_nesting("([Int], Int)", 1).is_err());
+        assert!(parse_type_with_max_nesting(r#"Float<"[[[">"#, 1).is_ok());
+    }
+}
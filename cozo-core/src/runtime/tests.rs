@@ -175,6 +175,44 @@ fn rm_does_not_need_all_keys() {
     assert!(db.run_default("?[uid] <- [[1]] :rm status {uid}").is_ok());
 }
 
+#[test]
+fn create_rejects_deeply_nested_type() {
+    let db = DbInstance::default();
+    let nested = |n: usize| format!("{}Int{}", "[".repeat(n), "]".repeat(n));
+    db.run_default(&format!(":create shallow {{k => v: {}}}", nested(100)))
+        .unwrap();
+    let err = db
+        .run_default(&format!(":create deep {{k => v: {}}}", nested(150)))
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Type is nested more than 128 levels deep");
+
+    // too deep for the parser itself, so rejected before parsing
+    for n in [5000, 10_000] {
+        let err = db
+            .run_default(&format!(":create deeper {{k => v: {}}}", nested(n)))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Script is nested more than 160 levels deep"
+        );
+    }
+    let parens = format!("{}1{}", "(".repeat(5000), ")".repeat(5000));
+    let err = db
+        .run_default(&format!("?[x] := x = {parens}"))
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Script is nested more than 160 levels deep"
+    );
+    let brackets_in_strings = format!(
+        "?[x, y] <- [['{}', _\"{}\"_]] # {}",
+        "[".repeat(5000),
+        "(\"".repeat(5000),
+        "{".repeat(5000)
+    );
+    db.run_default(&brackets_in_strings).unwrap();
+}
+
 #[test]
 fn strict_checks_for_fixed_rules_args() {
     let db = DbInstance::default();